@@ -0,0 +1,194 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
+
+use rustc_serialize::json::{ToJson, Json};
+use json_utils::{JsonMap, create_object};
+
+use super::OdeModel;
+use super::checker::CheckerContext;
+use super::formula::Proposition;
+use super::generator::StateSet2;
+use super::generator::initial_states;
+use super::generator::compute_directed_edges;
+use super::model::Model;
+use super::model::StateID;
+use super::model::TimeFlow;
+use super::parameters::Colors;
+use super::reachability::{ReachOptions, reachable};
+
+/// A terminal strongly connected component of the state transition graph.
+/// The component can differ between parameter values, so every state
+/// carries the colors for which it is part of the attractor.
+#[derive(Debug, Clone)]
+pub struct Attractor<C: Colors> {
+    pub states: StateSet2<C>,
+    /// Colors for which the attractor is terminal (union of the state colors).
+    pub colors: C
+}
+
+/// Compute all parametrised attractors (terminal SCCs) of the model.
+///
+/// Pivot based algorithm: the forward reachable set F of a pivot is an attractor for colors
+/// where every state of F can reach the pivot back. States that reach the pivot cannot belong
+/// to a different attractor, so the backward reachable set of the pivot is removed from
+/// the candidates in every step.
+pub fn attractors<C: Colors + Debug + Clone>(ctx: &mut CheckerContext<C>) -> Vec<Attractor<C>> {
+    let bounds = C::model_bounds(&ctx.model);
+    let mut candidates: StateSet2<C> = HashMap::new();
+    for (state, colors) in initial_states::<C>(&ctx.model, &Proposition::True) {
+        let colors = colors.and(&bounds);
+        if !colors.is_empty() {
+            candidates.insert(state, colors);
+        }
+    }
+    let mut result = vec![];
+    while let Some(pivot) = candidates.keys().min().cloned() {
+        let mut initial = HashMap::new();
+        initial.insert(pivot, candidates[&pivot].clone());
+        let forward = reach(ctx, &initial, None, true);
+        let backward = reach(ctx, &initial, None, false);
+        //colors for which some forward reachable state cannot reach the pivot
+        let escaping = forward.iter().fold(C::ff(), |acc, (state, colors)| {
+            match backward.get(state) {
+                Some(back) => acc.or(&colors.and(&back.not())),
+                None => acc.or(colors)
+            }
+        });
+        let terminal = initial[&pivot].and(&escaping.not());
+        if !terminal.is_empty() {
+            let mut states = HashMap::new();
+            for (state, colors) in forward {
+                let colors = colors.and(&terminal);
+                if !colors.is_empty() {
+                    states.insert(state, colors);
+                }
+            }
+            result.push(Attractor { states: states, colors: terminal });
+        }
+        candidates = minus(&candidates, &backward);
+    }
+    result
+}
+
+/// A parametrised strongly connected component. Every state carries the colors for which
+/// it belongs to the component.
+#[derive(Debug, Clone)]
+pub struct Component<C: Colors> {
+    pub states: StateSet2<C>,
+    /// Colors for which the component contains a cycle, that is, it has more than one state
+    /// or its only state has a self-loop.
+    pub cyclic: C
+}
+
+/// Decompose the state transition graph into parametrised strongly connected components.
+pub fn components<C: Colors + Debug + Clone>(ctx: &mut CheckerContext<C>) -> Vec<Component<C>> {
+    let bounds = C::model_bounds(&ctx.model);
+    let all: StateSet2<C> = initial_states::<C>(&ctx.model, &Proposition::True).into_iter().map(|(state, colors)| {
+        (state, colors.and(&bounds))
+    }).filter(|&(_, ref colors)| !colors.is_empty()).collect();
+    components_within(ctx, &all)
+}
+
+/// Strongly connected components of the subgraph induced by the given states and colors
+/// (forward-backward algorithm). Components are ordered by their smallest state.
+pub fn components_within<C: Colors + Debug + Clone>(
+    ctx: &mut CheckerContext<C>, within: &StateSet2<C>
+) -> Vec<Component<C>> {
+    let mut result = vec![];
+    let mut stack = vec![within.clone()];
+    while let Some(set) = stack.pop() {
+        let pivot = match set.keys().min() {
+            Some(pivot) => *pivot,
+            None => continue
+        };
+        let colors = set[&pivot].clone();
+        let mut initial = HashMap::new();
+        initial.insert(pivot, colors.clone());
+        let forward = reach(ctx, &initial, Some(&set), true);
+        //backward reachable states within the forward set form the component of the pivot
+        let component = reach(ctx, &initial, Some(&forward), false);
+        let self_loop = compute_directed_edges::<C>(ctx, &pivot, &true).get(&pivot).map(|c| c.and(&colors));
+        let cyclic = component.iter().filter(|&(state, _)| *state != pivot)
+            .fold(self_loop.unwrap_or(C::ff()), |acc, (_, c)| acc.or(c));
+        stack.push(minus(&set, &forward));
+        stack.push(minus(&forward, &component));
+        result.push(Component { states: component, cyclic: cyclic });
+    }
+    result.sort_by_key(|c| c.states.keys().min().cloned());
+    result
+}
+
+//Colors of the first set that are not in the second one.
+fn minus<C: Colors + Clone>(set: &StateSet2<C>, other: &StateSet2<C>) -> StateSet2<C> {
+    set.iter().filter_map(|(state, colors)| {
+        let colors = match other.get(state) {
+            Some(c) => colors.and(&c.not()),
+            None => colors.clone()
+        };
+        if colors.is_empty() { None } else { Some((*state, colors)) }
+    }).collect()
+}
+
+fn reach<C: Colors + Debug + Clone>(
+    ctx: &mut CheckerContext<C>, initial: &StateSet2<C>, within: Option<&StateSet2<C>>, time_flow: TimeFlow
+) -> StateSet2<C> {
+    let options = ReachOptions { time_flow: time_flow, within: within, target: None, max_depth: None };
+    reachable(ctx, initial.clone(), &options, |_, _| {}).states
+}
+
+/// Export the attractors in the format of the checker results: states and distinct
+/// colors are listed once and attractors refer to them by index.
+pub fn export_attractors<C>(model: &Model, full_model: &OdeModel, attractors: &[Attractor<C>]) -> Json
+    where C: Colors + Clone + ToJson
+{
+    let mut colors: Vec<C> = vec![];
+    let mut states: Vec<StateID> = vec![];
+    let mut state_indices: HashMap<StateID, usize> = HashMap::new();
+    let mut exported = vec![];
+    for attractor in attractors {
+        let mut data = vec![];
+        let mut sorted: Vec<&StateID> = attractor.states.keys().collect();
+        sorted.sort();
+        for state in sorted {
+            if !state_indices.contains_key(state) {
+                state_indices.insert(*state, states.len());
+                states.push(*state);
+            }
+            let param = color_index(&mut colors, &attractor.states[state]);
+            data.push(create_object(|map| {
+                map.write_item("state", &state_indices[state]);
+                map.write_item("param", &param);
+            }));
+        }
+        let param = color_index(&mut colors, &attractor.colors);
+        exported.push(create_object(|map| {
+            map.write_item("param", &param);
+            map.write_item("data", &data);
+        }));
+    }
+    create_object(|map| {
+        map.write_item("variables", &full_model.variables.iter().map(|i| i.name.clone()).collect::<Vec<String>>());
+        map.write_item("thresholds", &model.variables);
+        map.write_item("type", &"rectangular".to_string());
+        map.write_item("parameters", &full_model.parameters.iter().map(|i| i.name.clone()).collect::<Vec<String>>());
+        map.write_item("states", &states.iter().map(|state| {
+            create_object(|map| {
+                map.write_item("id", state);
+                map.write_item("bounds", &model.expand_state(state));
+            })
+        }).collect::<Vec<Json>>());
+        map.write_item("parameter_values", &colors);
+        map.write_item("attractors", &exported);
+    })
+}
+
+//Colors do not have to be hashable, so distinct colors are found by comparison.
+fn color_index<C: Colors + Clone>(colors: &mut Vec<C>, c: &C) -> usize {
+    match colors.iter().position(|i| i == c) {
+        Some(i) => i,
+        None => {
+            colors.push(c.clone());
+            colors.len() - 1
+        }
+    }
+}
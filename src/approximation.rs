@@ -0,0 +1,123 @@
+use super::Variable;
+use super::Point;
+
+/// Increasing Hill function: a + (b - a) * x^n / (x^n + theta^n)
+pub fn hill(x: f64, theta: f64, n: f64, a: f64, b: f64) -> f64 {
+    let xn = x.powf(n);
+    a + (b - a) * xn / (xn + theta.powf(n))
+}
+
+/// Increasing sigmoid function: a + (b - a) * (1 + tanh(k * (x - theta))) / 2
+pub fn sigmoid(x: f64, theta: f64, k: f64, a: f64, b: f64) -> f64 {
+    a + (b - a) * (1.0 + (k * (x - theta)).tanh()) / 2.0
+}
+
+/// Sample given function into a list of approximation points.
+///
+/// If the variable specifies var points, the function is sampled in `pointCount`
+/// points and `segmentCount` optimal linear segments are selected from them
+/// (see `optimal_segments`). Otherwise the function is sampled in the thresholds
+/// of the variable, which makes the approximation exact in every vertex of the state space.
+pub fn sample<F>(variable: &Variable, f: F) -> Vec<Point> where F: Fn(f64) -> f64 {
+    if let Some(ref var_points) = variable.var_points {
+        let count = if var_points.point_count < 2 { 2 } else { var_points.point_count };
+        let step = (variable.range.max - variable.range.min) / (count - 1) as f64;
+        let points: Vec<Point> = (0..count).map(|i| {
+            let x = if i == count - 1 { variable.range.max } else { variable.range.min + step * i as f64 };
+            Point { threshold: x, value: f(x) }
+        }).collect();
+        optimal_segments(&points, var_points.segment_count as usize)
+    } else {
+        variable.thresholds.iter().map(|x| Point { threshold: *x, value: f(*x) }).collect()
+    }
+}
+
+/// Select a subset of sampled points (always including the first and the last one)
+/// which splits the samples into at most `segment_count` linear segments
+/// with minimal total squared error.
+///
+/// Uses dynamic programming over the breakpoints, so the complexity is
+/// O(segment_count * points^2).
+pub fn optimal_segments(points: &[Point], segment_count: usize) -> Vec<Point> {
+    let n = points.len();
+    if n <= 2 || segment_count + 1 >= n {
+        return points.to_vec();
+    }
+    let segment_count = if segment_count == 0 { 1 } else { segment_count };
+    let errors = SegmentErrors::new(points);
+    //best[k][j] - minimal error of covering points 0..j with k segments ending in j
+    let mut best = vec![vec![::std::f64::INFINITY; n]; segment_count + 1];
+    let mut previous = vec![vec![0; n]; segment_count + 1];
+    best[0][0] = 0.0;
+    for k in 1..(segment_count + 1) {
+        for j in 1..n {
+            for i in 0..j {
+                if best[k - 1][i].is_finite() {
+                    let error = best[k - 1][i] + errors.between(i, j);
+                    if error < best[k][j] {
+                        best[k][j] = error;
+                        previous[k][j] = i;
+                    }
+                }
+            }
+        }
+    }
+    //fewer segments can be better only due to rounding, but they are always allowed
+    let mut k = (1..(segment_count + 1)).fold(1, |acc, k| {
+        if best[k][n - 1] < best[acc][n - 1] { k } else { acc }
+    });
+    let mut indices = vec![n - 1];
+    let mut j = n - 1;
+    while k > 0 {
+        j = previous[k][j];
+        indices.push(j);
+        k -= 1;
+    }
+    indices.iter().rev().map(|i| points[*i].clone()).collect()
+}
+
+//Prefix sums that allow computing the squared error of a segment in constant time.
+struct SegmentErrors<'a> {
+    points: &'a [Point],
+    x: Vec<f64>, y: Vec<f64>, xx: Vec<f64>, xy: Vec<f64>, yy: Vec<f64>
+}
+
+impl<'a> SegmentErrors<'a> {
+    fn new(points: &'a [Point]) -> SegmentErrors<'a> {
+        let mut sums = SegmentErrors {
+            points: points, x: vec![0.0], y: vec![0.0], xx: vec![0.0], xy: vec![0.0], yy: vec![0.0]
+        };
+        for &Point { threshold, value } in points {
+            let last = sums.x.len() - 1;
+            let (x, y, xx, xy, yy) = (sums.x[last], sums.y[last], sums.xx[last], sums.xy[last], sums.yy[last]);
+            sums.x.push(x + threshold);
+            sums.y.push(y + value);
+            sums.xx.push(xx + threshold * threshold);
+            sums.xy.push(xy + threshold * value);
+            sums.yy.push(yy + value * value);
+        }
+        sums
+    }
+
+    //Squared error of the points strictly between i and j when replaced by a line from i to j.
+    fn between(&self, i: usize, j: usize) -> f64 {
+        if j <= i + 1 {
+            return 0.0;
+        }
+        let start = &self.points[i];
+        let end = &self.points[j];
+        let slope = (end.value - start.value) / (end.threshold - start.threshold);
+        let shift = start.value - slope * start.threshold;
+        let count = (j - i - 1) as f64;
+        let (from, to) = (i + 1, j);
+        let x = self.x[to] - self.x[from];
+        let y = self.y[to] - self.y[from];
+        let xx = self.xx[to] - self.xx[from];
+        let xy = self.xy[to] - self.xy[from];
+        let yy = self.yy[to] - self.yy[from];
+        // sum (y - shift - slope * x)^2
+        let error = yy + shift * shift * count + slope * slope * xx
+            - 2.0 * shift * y - 2.0 * slope * xy + 2.0 * shift * slope * x;
+        error.max(0.0)
+    }
+}
@@ -0,0 +1,218 @@
+use std::collections::HashMap;
+
+use super::OdeModel;
+use super::Variable;
+use super::Parameter;
+use super::Range;
+use super::VarPoints;
+use super::parser::EquationParser;
+use super::parser::ParseError;
+use super::parser::write_equation;
+
+// The BIO format is a line based text format used by the Pithya/BioDivine tools:
+//
+// VARS: x, y
+// PARAMS: k1, 0, 2; k2, 0.5, 1
+// CONSTS: c, 0.5
+// THRES: x: 0, 1, 5
+// THRES: y: 0, 2
+// VAR_POINTS: x: 1500, 10
+// EQ: x = k1*hillp(y, 2, 3, 0, 1) - c*x
+// EQ: y = k2 - y
+//
+// Everything after # is a comment. Equations use the syntax of `parser::EquationParser`.
+// The BIO format has no variable ranges, so the range of each variable spans its thresholds.
+
+/// Read a model in the BIO format.
+pub fn read_bio(name: &str, text: &str) -> Result<OdeModel, ParseError> {
+    let lines: Vec<(usize, String, String, usize)> = try!(sections(text));
+    let mut variables: Vec<String> = vec![];
+    //where each variable is declared, so that missing sections can be reported there
+    let mut declarations: Vec<(usize, usize)> = vec![];
+    let mut parameters: Vec<Parameter> = vec![];
+    let mut constants: HashMap<String, f64> = HashMap::new();
+    //declarations first, so that the order of sections does not matter
+    for &(line, ref keyword, ref content, column) in &lines {
+        match keyword.as_ref() {
+            "VARS" => {
+                for (item, c) in split(content, ',', column) {
+                    variables.push(item);
+                    declarations.push((line, c));
+                }
+            }
+            "PARAMS" => {
+                for (item, c) in split(content, ';', column) {
+                    let values = split(&item, ',', c);
+                    if values.len() != 3 {
+                        return error(line, c, format!("Expected name, min, max, found {}", item));
+                    }
+                    parameters.push(Parameter {
+                        name: values[0].0.clone(),
+                        range: Range {
+                            min: try!(number(&values[1], line)),
+                            max: try!(number(&values[2], line))
+                        }
+                    });
+                }
+            }
+            "CONSTS" => {
+                for (item, c) in split(content, ';', column) {
+                    let values = split(&item, ',', c);
+                    if values.len() != 2 {
+                        return error(line, c, format!("Expected name, value, found {}", item));
+                    }
+                    constants.insert(values[0].0.clone(), try!(number(&values[1], line)));
+                }
+            }
+            "THRES" | "VAR_POINTS" | "EQ" => {}
+            other => return error(line, 1, format!("Unknown section: {}", other))
+        }
+    }
+    let mut parser = EquationParser::new(variables.clone(), parameters.iter().map(|p| p.name.clone()).collect());
+    parser.constants = constants;
+    let mut thresholds: Vec<Option<Vec<f64>>> = vec![None; variables.len()];
+    let mut var_points: Vec<Option<VarPoints>> = vec![None; variables.len()];
+    let mut equations = vec![None; variables.len()];
+    for &(line, ref keyword, ref content, column) in &lines {
+        match keyword.as_ref() {
+            "THRES" => {
+                let (variable, values, c) = try!(variable_item(&variables, content, line, column));
+                if thresholds[variable].is_some() {
+                    return error(line, column, format!("Duplicate thresholds of variable {}", variables[variable]));
+                }
+                let mut t = vec![];
+                for value in split(&values, ',', c) {
+                    t.push(try!(number(&value, line)));
+                }
+                thresholds[variable] = Some(t);
+            }
+            "VAR_POINTS" => {
+                let (variable, values, c) = try!(variable_item(&variables, content, line, column));
+                let values = split(&values, ',', c);
+                if values.len() != 2 {
+                    return error(line, c, "Expected point count and segment count".to_string());
+                }
+                var_points[variable] = Some(VarPoints {
+                    point_count: try!(count(&values[0], line)),
+                    segment_count: try!(count(&values[1], line))
+                });
+            }
+            "EQ" => {
+                match try!(parser.parse_equation_at(content, line, column)) {
+                    (Some(variable), equation) => equations[variable] = Some(equation),
+                    (None, _) => return error(line, column, "Expected x = prefix".to_string())
+                }
+            }
+            _ => {}
+        }
+    }
+    let mut result = vec![];
+    for (i, name) in variables.into_iter().enumerate() {
+        let (line, column) = declarations[i];
+        let t = match thresholds[i].take() {
+            Some(t) => t,
+            None => return error(line, column, format!("Missing thresholds of variable {}", name))
+        };
+        let equation = match equations[i].take() {
+            Some(e) => e,
+            None => return error(line, column, format!("Missing equation of variable {}", name))
+        };
+        let range = Range {
+            min: t.iter().cloned().fold(::std::f64::INFINITY, f64::min),
+            max: t.iter().cloned().fold(::std::f64::NEG_INFINITY, f64::max)
+        };
+        result.push(Variable {
+            name: name, range: range, thresholds: t, var_points: var_points[i].take(), equation: equation
+        });
+    }
+    Ok(OdeModel { name: name.to_string(), variables: result, parameters: parameters })
+}
+
+/// Write model in the BIO format. Variable ranges are not part of the format,
+/// so they are lost unless they match the first and last threshold.
+pub fn write_bio(model: &OdeModel) -> String {
+    let variables: Vec<String> = model.variables.iter().map(|v| v.name.clone()).collect();
+    let parameters: Vec<String> = model.parameters.iter().map(|p| p.name.clone()).collect();
+    let mut result = String::new();
+    result.push_str(&format!("VARS: {}\n", variables.join(", ")));
+    if !model.parameters.is_empty() {
+        let params: Vec<String> = model.parameters.iter().map(|p| {
+            format!("{}, {}, {}", p.name, p.range.min, p.range.max)
+        }).collect();
+        result.push_str(&format!("PARAMS: {}\n", params.join("; ")));
+    }
+    for var in &model.variables {
+        let t: Vec<String> = var.thresholds.iter().map(|t| format!("{}", t)).collect();
+        result.push_str(&format!("THRES: {}: {}\n", var.name, t.join(", ")));
+    }
+    for var in &model.variables {
+        if let Some(ref points) = var.var_points {
+            result.push_str(&format!("VAR_POINTS: {}: {}, {}\n", var.name, points.point_count, points.segment_count));
+        }
+    }
+    for var in &model.variables {
+        result.push_str(&format!("EQ: {} = {}\n", var.name, write_equation(&var.equation, &variables, &parameters)));
+    }
+    result
+}
+
+//Split the text into (line, keyword, content, content column) tuples, skipping comments and empty lines.
+fn sections(text: &str) -> Result<Vec<(usize, String, String, usize)>, ParseError> {
+    let mut result = vec![];
+    for (i, line) in text.lines().enumerate() {
+        let line_number = i + 1;
+        let line = line.split('#').next().unwrap();
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Some(colon) = line.find(':') {
+            let column = line[..(colon + 1)].chars().count() + 1;
+            result.push((line_number, line[..colon].trim().to_string(), line[(colon + 1)..].to_string(), column));
+        } else {
+            return error(line_number, 1, "Expected SECTION: content".to_string());
+        }
+    }
+    Ok(result)
+}
+
+//Split text by separator into trimmed non-empty items along with their starting columns.
+fn split(text: &str, separator: char, column: usize) -> Vec<(String, usize)> {
+    let mut result = vec![];
+    let mut offset = column;
+    for item in text.split(separator) {
+        let leading = item.chars().take_while(|c| c.is_whitespace()).count();
+        if !item.trim().is_empty() {
+            result.push((item.trim().to_string(), offset + leading));
+        }
+        offset += item.chars().count() + 1;
+    }
+    result
+}
+
+fn number(item: &(String, usize), line: usize) -> Result<f64, ParseError> {
+    item.0.parse::<f64>().or_else(|_| error(line, item.1, format!("Invalid number: {}", item.0)))
+}
+
+fn count(item: &(String, usize), line: usize) -> Result<u64, ParseError> {
+    item.0.parse::<u64>().or_else(|_| error(line, item.1, format!("Invalid count: {}", item.0)))
+}
+
+//Parse "name: content" where name is a declared variable.
+fn variable_item(variables: &Vec<String>, content: &str, line: usize, column: usize)
+    -> Result<(usize, String, usize), ParseError>
+{
+    if let Some(colon) = content.find(':') {
+        let name = content[..colon].trim();
+        let c = column + content[..(colon + 1)].chars().count();
+        match variables.iter().position(|v| v == name) {
+            Some(i) => Ok((i, content[(colon + 1)..].to_string(), c)),
+            None => error(line, column, format!("Unknown variable: {}", name))
+        }
+    } else {
+        error(line, column, "Expected variable: values".to_string())
+    }
+}
+
+fn error<T>(line: usize, column: usize, message: String) -> Result<T, ParseError> {
+    Err(ParseError { line: line, column: column, message: message })
+}
@@ -0,0 +1,536 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::env;
+use std::fmt::{Debug, Display, Formatter, Error};
+use std::fs;
+use std::io;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+use std::process;
+use std::process::Command;
+
+use rustc_serialize::json::{ToJson, Json};
+use json_utils::{FromJson, JsonMap, create_object};
+
+use super::safe_add;
+use super::fair_edges;
+use super::Fairness;
+use super::super::formula::Formula;
+use super::super::formula::Formula::*;
+use super::super::formula::Proposition;
+use super::super::generator::StateSet2;
+use super::super::generator::initial_states_part;
+use super::super::generator::directed_edges;
+use super::super::model::Model;
+use super::super::model::CompileError;
+use super::super::model::StateID;
+use super::super::model::TimeFlow;
+use super::super::parameters::Colors;
+
+// Distributed checking runs one coordinator and several worker processes on the same machine.
+// Worker `index` owns the states with `state % count == index` and keeps the results and
+// the edge cache only for these states. All workers evaluate the same formulas in lockstep.
+// Whenever colors have to be pushed to states of other workers, the workers send them to the
+// coordinator, which forwards them to their owners. Colors pushed to owned states are merged
+// without the coordinator. The coordinator also decides when
+// a fixpoint is reached and collects the final results.
+//
+// Messages are JSON objects, one per line, sent over a Unix socket:
+// worker -> coordinator: {"type": "hello", "index": i}
+//                        {"type": "exchange", "outboxes": [[[predecessor, state, colors], ...], ...]}
+//                        {"type": "active", "value": bool}
+//                        {"type": "result", "states": [[state, colors], ...]}
+// coordinator -> worker: {"inbox": [[predecessor, state, colors], ...]}
+//                        {"continue": bool}
+
+#[derive(Debug)]
+pub enum DistributedError {
+    Io(io::Error),
+    Compile(CompileError),
+    Protocol(String),
+    Worker(String)
+}
+
+impl Display for DistributedError {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        match self {
+            &DistributedError::Io(ref e) => write!(f, "Communication error: {}", e),
+            &DistributedError::Compile(ref e) => write!(f, "Unsupported model: {}", e),
+            &DistributedError::Protocol(ref message) => write!(f, "Invalid message: {}", message),
+            &DistributedError::Worker(ref message) => write!(f, "Worker failed: {}", message)
+        }
+    }
+}
+
+impl From<io::Error> for DistributedError {
+    fn from(error: io::Error) -> DistributedError {
+        DistributedError::Io(error)
+    }
+}
+
+impl From<CompileError> for DistributedError {
+    fn from(error: CompileError) -> DistributedError {
+        DistributedError::Compile(error)
+    }
+}
+
+fn protocol<T>(message: &str, json: &Json) -> Result<T, DistributedError> {
+    Err(DistributedError::Protocol(format!("{}: {}", message, json)))
+}
+
+/// JSON line connection over a Unix socket.
+pub struct Channel {
+    reader: BufReader<UnixStream>,
+    writer: UnixStream
+}
+
+impl Channel {
+    pub fn new(stream: UnixStream) -> Result<Channel, DistributedError> {
+        let writer = try!(stream.try_clone());
+        Ok(Channel { reader: BufReader::new(stream), writer: writer })
+    }
+
+    pub fn send(&mut self, json: &Json) -> Result<(), DistributedError> {
+        try!(writeln!(self.writer, "{}", json));
+        try!(self.writer.flush());
+        Ok(())
+    }
+
+    pub fn receive(&mut self) -> Result<Json, DistributedError> {
+        let mut line = String::new();
+        if try!(self.reader.read_line(&mut line)) == 0 {
+            return Err(DistributedError::Protocol("Connection closed".to_string()));
+        }
+        Json::from_str(&line).map_err(|e| DistributedError::Protocol(e.to_string()))
+    }
+}
+
+/// Run `count` worker processes of the current executable and coordinate them until
+/// results for `formula_count` formulas are received. Workers are started with arguments
+/// `--worker <socket> <index> <count>` followed by `worker_args` and should call `run_worker`.
+pub fn check_distributed<C: Colors + FromJson<C>>(
+    count: usize, formula_count: usize, worker_args: &[String]
+) -> Result<Vec<StateSet2<C>>, DistributedError> {
+    let socket = env::temp_dir().join(format!("ode_model_{}.sock", process::id()));
+    let _ = fs::remove_file(&socket);
+    let listener = try!(UnixListener::bind(&socket));
+    let executable = try!(env::current_exe());
+    let mut children = vec![];
+    for index in 0..count {
+        children.push(try!(Command::new(&executable)
+            .arg("--worker").arg(&socket).arg(index.to_string()).arg(count.to_string())
+            .args(worker_args)
+            .spawn()));
+    }
+    let result = coordinate(&listener, count, formula_count);
+    for mut child in children {
+        if result.is_err() {
+            let _ = child.kill();
+        }
+        let status = try!(child.wait());
+        if result.is_ok() && !status.success() {
+            return Err(DistributedError::Worker(format!("exit status {}", status)));
+        }
+    }
+    let _ = fs::remove_file(&socket);
+    result
+}
+
+/// Accept `count` workers on the listener and relay their messages until results for
+/// `formula_count` formulas are received. Used by `check_distributed`, the workers can also
+/// run in threads of the current process.
+pub fn coordinate<C: Colors + FromJson<C>>(listener: &UnixListener, count: usize, formula_count: usize)
+    -> Result<Vec<StateSet2<C>>, DistributedError>
+{
+    let mut channels: Vec<Option<Channel>> = (0..count).map(|_| None).collect();
+    for _ in 0..count {
+        let (stream, _) = try!(listener.accept());
+        let mut channel = try!(Channel::new(stream));
+        let hello = try!(channel.receive());
+        match hello.find("index").and_then(|i| i.as_u64()) {
+            Some(i) if (i as usize) < count && channels[i as usize].is_none() => channels[i as usize] = Some(channel),
+            _ => return protocol("Expected hello", &hello)
+        }
+    }
+    let mut channels: Vec<Channel> = channels.into_iter().map(|c| c.unwrap()).collect();
+    let mut results = vec![];
+    while results.len() < formula_count {
+        let mut messages = vec![];
+        for channel in &mut channels {
+            messages.push(try!(channel.receive()));
+        }
+        let kind = messages[0].find("type").and_then(|t| t.as_string()).unwrap_or("").to_string();
+        if messages.iter().any(|m| m.find("type").and_then(|t| t.as_string()) != Some(kind.as_ref())) {
+            return protocol("Workers are out of sync", &messages[0]);
+        }
+        match kind.as_ref() {
+            "exchange" => {
+                let mut inboxes: Vec<Vec<Json>> = vec![vec![]; count];
+                for message in &messages {
+                    match message.find("outboxes").and_then(|o| o.as_array()) {
+                        Some(outboxes) if outboxes.len() == count => {
+                            for (inbox, outbox) in inboxes.iter_mut().zip(outboxes.iter()) {
+                                inbox.extend(outbox.as_array().cloned().unwrap_or(vec![]));
+                            }
+                        }
+                        _ => return protocol("Expected outboxes", message)
+                    }
+                }
+                for (channel, inbox) in channels.iter_mut().zip(inboxes.into_iter()) {
+                    try!(channel.send(&create_object(|map| {
+                        map.write_item("inbox", &inbox);
+                    })));
+                }
+            }
+            "active" => {
+                let active = messages.iter().any(|m| m.find("value").and_then(|v| v.as_boolean()) == Some(true));
+                for channel in &mut channels {
+                    try!(channel.send(&create_object(|map| {
+                        map.write_item("continue", &active);
+                    })));
+                }
+            }
+            "result" => {
+                let mut result = HashMap::new();
+                for message in &messages {
+                    for item in message.find("states").and_then(|s| s.as_array()).unwrap_or(&vec![]) {
+                        let (state, colors) = try!(decode_state(item));
+                        result.insert(state, colors);
+                    }
+                }
+                results.push(result);
+            }
+            _ => return protocol("Unknown message", &messages[0])
+        }
+    }
+    Ok(results)
+}
+
+fn decode_state<C: FromJson<C>>(item: &Json) -> Result<(StateID, C), DistributedError> {
+    match item.as_array() {
+        Some(pair) if pair.len() == 2 => {
+            match (pair[0].as_u64(), C::from_json(&pair[1])) {
+                (Some(state), Ok(colors)) => Ok((state as StateID, colors)),
+                _ => protocol("Expected state and colors", item)
+            }
+        }
+        _ => protocol("Expected state and colors", item)
+    }
+}
+
+/// Connect to the coordinator, evaluate all formulas on the owned part of the state space
+/// and send the results to the coordinator. All workers have to use the same fairness.
+pub fn run_worker<C: Colors + Debug + Clone + ToJson + FromJson<C>>(
+    socket: &Path, index: usize, count: usize, model: Model, fairness: Fairness, formulas: &[&Formula]
+) -> Result<(), DistributedError> {
+    let mut ctx = try!(WorkerContext::<C>::connect(socket, model, index, count));
+    ctx.fairness = fairness;
+    for formula in formulas {
+        let result = try!(check_worker(&mut ctx, formula));
+        let mut states: Vec<(StateID, C)> = result.into_iter().collect();
+        states.sort_by(|a, b| a.0.cmp(&b.0));
+        let states: Vec<Json> = states.into_iter().map(|(s, c)| Json::Array(vec![s.to_json(), c.to_json()])).collect();
+        try!(ctx.channel.send(&create_object(|map| {
+            map.write_item("type", &"result".to_string());
+            map.write_item("states", &states);
+        })));
+    }
+    Ok(())
+}
+
+pub struct WorkerContext<C: Colors> {
+    pub model: Model,
+    pub index: usize,
+    pub count: usize,
+    pub fairness: Fairness,
+    channel: Channel,
+    edge_cache: HashMap<(StateID, TimeFlow), StateSet2<C>>
+}
+
+//Pushed colors: (predecessor, state, colors of the edge and the state)
+type Inbox<C> = Vec<(StateID, StateID, C)>;
+
+impl<C: Colors + Debug + Clone + ToJson + FromJson<C>> WorkerContext<C> {
+    pub fn connect(socket: &Path, model: Model, index: usize, count: usize) -> Result<WorkerContext<C>, DistributedError> {
+        try!(C::supports(&model));
+        let mut channel = try!(Channel::new(try!(UnixStream::connect(socket))));
+        try!(channel.send(&create_object(|map| {
+            map.write_item("type", &"hello".to_string());
+            map.write_item("index", &index);
+        })));
+        Ok(WorkerContext {
+            model: model, index: index, count: count, fairness: Fairness::Disabled,
+            channel: channel, edge_cache: HashMap::new()
+        })
+    }
+
+    //Edges are only computed for owned states, so the cache holds only the owned part of the graph.
+    fn edges(&mut self, state: &StateID, time_flow: &TimeFlow) -> StateSet2<C> {
+        if let Some(edges) = self.edge_cache.get(&(*state, *time_flow)) {
+            return edges.clone();
+        }
+        //the model is checked in `connect`
+        let edges = directed_edges::<C>(&self.model, state, time_flow)
+            .expect("Colors do not support the model of the worker");
+        self.edge_cache.insert((*state, *time_flow), edges.clone());
+        edges
+    }
+
+    fn owned_states(&self, prop: &Proposition) -> StateSet2<C> {
+        initial_states_part::<C>(&self.model, prop, self.index, self.count)
+    }
+
+    //Push colors of the frontier states to their predecessors and exchange them with other workers.
+    //Pushes to owned states stay local, the outbox of this worker is always empty.
+    //Returns pushed colors addressed to this worker, sorted by (predecessor, state).
+    fn expand(&mut self, set: &StateSet2<C>, frontier: &Vec<StateID>) -> Result<Inbox<C>, DistributedError> {
+        let mut outboxes: Vec<Vec<Json>> = vec![vec![]; self.count];
+        let mut inbox = vec![];
+        for state in frontier {
+            for (predecessor, edge_colors) in self.edges(state, &false) {
+                let pushed = edge_colors.and(&set[state]);
+                if pushed.is_empty() {
+                    continue;
+                }
+                if predecessor % self.count == self.index {
+                    inbox.push((predecessor, *state, pushed));
+                } else {
+                    outboxes[predecessor % self.count].push(Json::Array(vec![
+                        predecessor.to_json(), state.to_json(), pushed.to_json()
+                    ]));
+                }
+            }
+        }
+        try!(self.channel.send(&create_object(|map| {
+            map.write_item("type", &"exchange".to_string());
+            map.write_item("outboxes", &outboxes);
+        })));
+        let message = try!(self.channel.receive());
+        for item in message.find("inbox").and_then(|i| i.as_array()).unwrap_or(&vec![]) {
+            match item.as_array() {
+                Some(triple) if triple.len() == 3 => {
+                    match (triple[0].as_u64(), triple[1].as_u64(), C::from_json(&triple[2])) {
+                        (Some(predecessor), Some(state), Ok(colors)) => {
+                            inbox.push((predecessor as StateID, state as StateID, colors))
+                        }
+                        _ => return protocol("Expected pushed colors", item)
+                    }
+                }
+                _ => return protocol("Expected pushed colors", item)
+            }
+        }
+        inbox.sort_by(|a, b| (a.0, a.1).cmp(&(b.0, b.1)));
+        Ok(inbox)
+    }
+
+    //Tell the coordinator whether this worker has more work and learn whether any worker has.
+    fn any_active(&mut self, active: bool) -> Result<bool, DistributedError> {
+        try!(self.channel.send(&create_object(|map| {
+            map.write_item("type", &"active".to_string());
+            map.write_item("value", &active);
+        })));
+        let message = try!(self.channel.receive());
+        match message.find("continue").and_then(|c| c.as_boolean()) {
+            Some(active) => Ok(active),
+            None => protocol("Expected continue", &message)
+        }
+    }
+}
+
+/// Evaluate the formula on the states owned by this worker. All workers have to evaluate
+/// the same formula at the same time.
+pub fn check_worker<C: Colors + Debug + Clone + ToJson + FromJson<C>>(
+    ctx: &mut WorkerContext<C>, formula: &Formula
+) -> Result<StateSet2<C>, DistributedError> {
+    let tt = || Box::new(Atom(Proposition::True));
+    match formula {
+        &Atom(ref prop) => Ok(ctx.owned_states(prop)),
+        &Not(ref inner) => {
+            let inner = try!(check_worker(ctx, inner));
+            let bounds = C::model_bounds(&ctx.model);
+            let mut result = HashMap::new();
+            for (state, colors) in ctx.owned_states(&Proposition::True) {
+                let colors = match inner.get(&state) {
+                    Some(c) => colors.and(&c.not()).and(&bounds),
+                    None => colors
+                };
+                if !colors.is_empty() {
+                    result.insert(state, colors);
+                }
+            }
+            Ok(result)
+        }
+        &And(ref left, ref right) => {
+            let left = try!(check_worker(ctx, left));
+            let right = try!(check_worker(ctx, right));
+            let bounds = C::model_bounds(&ctx.model);
+            let mut result = HashMap::new();
+            for (state, colors) in left {
+                if let Some(c) = right.get(&state) {
+                    let colors = colors.and(c).and(&bounds);
+                    if !colors.is_empty() {
+                        result.insert(state, colors);
+                    }
+                }
+            }
+            Ok(result)
+        }
+        &Or(ref left, ref right) => {
+            let mut result = try!(check_worker(ctx, left));
+            for (state, colors) in try!(check_worker(ctx, right)) {
+                safe_add(&mut result, state, colors);
+            }
+            Ok(result)
+        }
+        &Implies(ref left, ref right) => check_worker(ctx, &Or(Box::new(Not(left.clone())), right.clone())),
+        &EX(ref inner) => {
+            let inner = try!(check_worker(ctx, inner));
+            let bounds = C::model_bounds(&ctx.model);
+            let mut frontier: Vec<StateID> = inner.keys().cloned().collect();
+            frontier.sort();
+            let mut result = HashMap::new();
+            for (predecessor, _, pushed) in try!(ctx.expand(&inner, &frontier)) {
+                safe_add(&mut result, predecessor, pushed.and(&bounds));
+            }
+            Ok(result)
+        }
+        &AX(ref inner) => check_worker(ctx, &Not(Box::new(EX(Box::new(Not(inner.clone())))))),
+        &EF(ref inner) => check_worker(ctx, &EU(tt(), inner.clone())),
+        &AF(ref inner) => check_worker(ctx, &AU(tt(), inner.clone())),
+        &EG(ref inner) => {
+            let bounds = C::model_bounds(&ctx.model);
+            let mut initial = HashMap::new();
+            for (state, colors) in try!(check_worker(ctx, inner)) {
+                let colors = colors.and(&bounds);
+                if !colors.is_empty() {
+                    initial.insert(state, colors);
+                }
+            }
+            globally(ctx, initial)
+        }
+        &AG(ref inner) => check_worker(ctx, &Not(Box::new(EF(Box::new(Not(inner.clone())))))),
+        &EU(ref path, ref reach) => {
+            let path = try!(check_worker(ctx, path));
+            let reach = try!(check_worker(ctx, reach));
+            until(ctx, path, reach, false, None)
+        }
+        &AU(ref path, ref reach) => {
+            let path = try!(check_worker(ctx, path));
+            let reach = try!(check_worker(ctx, reach));
+            until(ctx, path, reach, true, None)
+        }
+        &BoundedEF(ref inner, bound) => check_worker(ctx, &BoundedEU(tt(), inner.clone(), bound)),
+        &BoundedEU(ref path, ref reach, bound) => {
+            let path = try!(check_worker(ctx, path));
+            let reach = try!(check_worker(ctx, reach));
+            until(ctx, path, reach, false, Some(bound))
+        }
+        &Var(_) | &Bind(..) | &At(..) | &Exists(..) => {
+            Err(DistributedError::Worker(format!("Hybrid operators are not supported: {:?}", formula)))
+        }
+    }
+}
+
+//Same rounds as in `parallel::until`, but the workers are separate processes.
+fn until<C: Colors + Debug + Clone + ToJson + FromJson<C>>(
+    ctx: &mut WorkerContext<C>, path: StateSet2<C>, reach: StateSet2<C>, all: bool, bound: Option<usize>
+) -> Result<StateSet2<C>, DistributedError> {
+    let bounds = C::model_bounds(&ctx.model);
+    let mut result = reach;
+    let mut frontier: Vec<StateID> = result.keys().cloned().collect();
+    frontier.sort();
+    let mut uncovered: HashMap<StateID, StateSet2<C>> = HashMap::new();
+    //all workers count the same rounds, so they stop at the bound together
+    let mut rounds = 0;
+    while bound.map_or(true, |b| rounds < b) && try!(ctx.any_active(!frontier.is_empty())) {
+        rounds += 1;
+        let inbox = try!(ctx.expand(&result, &frontier));
+        let mut changed = HashSet::new();
+        for (predecessor, state, pushed) in inbox {
+            let mut valid = pushed.and(path.get(&predecessor).unwrap_or(&C::ff())).and(&bounds);
+            if all {
+                if !uncovered.contains_key(&predecessor) {
+                    let successors = fair_edges(&predecessor, ctx.edges(&predecessor, &true), ctx.fairness);
+                    uncovered.insert(predecessor, successors);
+                }
+                let successors = uncovered.get_mut(&predecessor).unwrap();
+                //the edge can be missing if it is an unfair self-loop
+                if let Some(new_uncovered) = successors.get(&state).map(|c| c.and(&pushed.not())) {
+                    successors.insert(state, new_uncovered);
+                }
+                let total_uncovered = successors.values().fold(C::ff(), |acc, i| acc.or(i));
+                valid = valid.and(&total_uncovered.not());
+            }
+            if safe_add(&mut result, predecessor, valid) {
+                changed.insert(predecessor);
+            }
+        }
+        frontier = changed.into_iter().collect();
+        frontier.sort();
+    }
+    Ok(result)
+}
+
+//Same rounds as in `parallel::merge_globally`: the whole set is pushed in the first round,
+//then only the colors removed in the previous round.
+fn globally<C: Colors + Debug + Clone + ToJson + FromJson<C>>(
+    ctx: &mut WorkerContext<C>, initial: StateSet2<C>
+) -> Result<StateSet2<C>, DistributedError> {
+    let mut result = initial;
+    let mut removed = result.clone();
+    let mut frontier: Vec<StateID> = result.keys().cloned().collect();
+    frontier.sort();
+    //colors of successors that are still in the set
+    let mut in_set: HashMap<StateID, StateSet2<C>> = HashMap::new();
+    let mut first = true;
+    while try!(ctx.any_active(!frontier.is_empty())) {
+        let inbox = try!(ctx.expand(&removed, &frontier));
+        let mut touched: HashSet<StateID> = if first { result.keys().cloned().collect() } else { HashSet::new() };
+        for (predecessor, state, pushed) in inbox {
+            if !result.contains_key(&predecessor) {
+                continue;
+            }
+            let colors = if first {
+                if predecessor == state {
+                    match fair_edges(&predecessor, ctx.edges(&predecessor, &true), ctx.fairness).remove(&state) {
+                        Some(fair) => pushed.and(&fair),
+                        None => continue
+                    }
+                } else {
+                    pushed
+                }
+            } else {
+                match in_set.get(&predecessor).and_then(|successors| successors.get(&state)) {
+                    Some(colors) => colors.and(&pushed.not()),
+                    None => continue
+                }
+            };
+            in_set.entry(predecessor).or_insert_with(HashMap::new).insert(state, colors);
+            touched.insert(predecessor);
+        }
+        first = false;
+        removed.clear();
+        frontier.clear();
+        for state in touched {
+            let old = result[&state].clone();
+            let has_successor = in_set.get(&state).map_or(C::ff(), |successors| {
+                successors.values().fold(C::ff(), |acc, c| acc.or(c))
+            });
+            let lost = old.and(&has_successor.not());
+            if lost.is_empty() {
+                continue;
+            }
+            let new = old.and(&has_successor);
+            if new.is_empty() {
+                result.remove(&state);
+            } else {
+                result.insert(state, new);
+            }
+            removed.insert(state, lost);
+            frontier.push(state);
+        }
+        frontier.sort();
+    }
+    Ok(result)
+}
@@ -1,40 +1,158 @@
 use std::collections::HashMap;
+use std::collections::HashSet;
 use std::collections::hash_map::Entry;
-use std::fmt::Debug;
+use std::fmt::{Debug, Display, Formatter, Error};
 
 use super::formula::Formula;
 use super::formula::Proposition;
 use super::generator::*;
 use super::model::Model;
+use super::model::CompileError;
+use super::model::VariableIndex;
 use super::model::StateID;
 use super::model::Facet;
 use super::model::TimeFlow;
 use super::parameters::Colors;
+use super::symbolic::Transitions;
 
 use super::formula::Formula::*;
 
+pub mod witness;
+pub mod parallel;
+pub mod distributed;
+
+///Fairness assumption applied to infinite paths (EG, AU and operators derived from them).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Fairness {
+    ///Every path of the state transition graph is considered.
+    Disabled,
+    ///Paths that stay forever in a state using a self-loop, even though the state
+    ///has other successors for the same colors, are ignored. Such self-loops are
+    ///usually spurious products of the rectangular abstraction.
+    NoTransientLoops
+}
+
 pub struct CheckerContext<C: Colors> {
     pub model: Model,
+    pub fairness: Fairness,
     pub facet_cache: HashMap<Facet, C>,
-    pub edge_cache: HashMap<(StateID, TimeFlow), StateSet2<C>>
+    pub edge_cache: HashMap<(StateID, TimeFlow), StateSet2<C>>,
+    ///Transition relation used by `SymbolicSet::pre`, built on first use.
+    pub symbolic_edges: Option<Transitions<C>>
+}
+
+///Reasons why a formula can't be checked on a model.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CheckError {
+    ///Parameter constraint that can't be represented by the colors.
+    UnsupportedConstraint(Proposition),
+    ///State variable that is not bound by any operator.
+    UnboundVariable(String),
+    ///Real value of a proposition lies between two thresholds of the variable,
+    ///so the proposition would be under-approximated (see `OdeModel::compile_for`).
+    NotOnThreshold { variable: VariableIndex, value: f64 },
+    ///Operator that the chosen checker can't evaluate, for example a hybrid operator
+    ///in the parallel checker.
+    UnsupportedOperator(Formula)
+}
+
+impl Display for CheckError {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        match self {
+            &CheckError::UnsupportedConstraint(ref prop) => write!(f, "Unsupported parameter constraint: {:?}", prop),
+            &CheckError::UnboundVariable(ref name) => write!(f, "Unbound state variable: {}", name),
+            &CheckError::NotOnThreshold { variable, value } =>
+                write!(f, "Value {} of variable {} is not a threshold", value, variable),
+            &CheckError::UnsupportedOperator(ref formula) => write!(f, "Unsupported operator: {:?}", formula)
+        }
+    }
+}
+
+///Check that all parts of the formula can be evaluated exactly with given colors. Parameter
+///constraints and state variables that fail this check are satisfied by no state in `check`,
+///real-valued propositions are under-approximated by the states entirely on the valid side.
+pub fn validate<C: Colors>(model: &Model, formula: &Formula) -> Result<(), CheckError> {
+    if let Some(name) = formula.free_variables().into_iter().next() {
+        return Err(CheckError::UnboundVariable(name));
+    }
+    for prop in formula.propositions() {
+        let (variable, values) = match prop {
+            &Proposition::Parameters(ref coefficients, ref op, value) => {
+                if C::parameter_constraint(model, coefficients, op, value).is_none() {
+                    return Err(CheckError::UnsupportedConstraint(prop.clone()));
+                }
+                continue
+            }
+            &Proposition::Value(variable, _, value) => (variable, vec![value]),
+            &Proposition::Range(variable, low, high) => (variable, vec![low, high]),
+            _ => continue
+        };
+        let thresholds = &model.variables[variable];
+        for value in values {
+            //values outside of the threshold span describe all or no states exactly
+            let inside = thresholds.first().map_or(false, |first| value > *first) &&
+                thresholds.last().map_or(false, |last| value < *last);
+            if inside && !thresholds.contains(&value) {
+                return Err(CheckError::NotOnThreshold { variable: variable, value: value });
+            }
+        }
+    }
+    Ok(())
 }
 
 impl <C: Colors> CheckerContext<C> {
-    pub fn new(model: Model) -> CheckerContext<C> {
-        CheckerContext {
+    ///Create a context for a model. Fails if the colors can't represent the parameter
+    ///constraints of the model (see `Colors::supports`).
+    pub fn new(model: Model) -> Result<CheckerContext<C>, CompileError> {
+        try!(C::supports(&model));
+        Ok(CheckerContext {
             facet_cache: HashMap::new(),
             edge_cache: HashMap::new(),
+            fairness: Fairness::Disabled,
+            symbolic_edges: None,
             model: model
+        })
+    }
+}
+
+///Successors of a state with respect to the fairness mode of the context.
+///With `NoTransientLoops`, the self-loop is kept only for colors that have no other successor.
+pub fn fair_successors<C: Colors + Debug + Clone>(ctx: &mut CheckerContext<C>, state: &StateID) -> StateSet2<C> {
+    let successors = compute_directed_edges::<C>(ctx, state, &true);
+    fair_edges(state, successors, ctx.fairness)
+}
+
+//Successors restricted by the fairness mode, also used by the parallel and distributed checkers.
+fn fair_edges<C: Colors>(state: &StateID, mut successors: StateSet2<C>, fairness: Fairness) -> StateSet2<C> {
+    if fairness == Fairness::NoTransientLoops {
+        if let Some(self_loop) = successors.remove(state) {
+            let leaving = successors.values().fold(C::ff(), |acc, c| acc.or(c));
+            let stay = self_loop.and(&leaving.not());
+            if !stay.is_empty() {
+                successors.insert(*state, stay);
+            }
         }
     }
+    successors
 }
 
 pub fn check<C: Colors + Debug + Clone>(ctx: &mut CheckerContext<C>, formula: &Formula) -> StateSet2<C> {
+    check_in::<C>(ctx, formula, &HashMap::new())
+}
+
+///Assignment of state variables of hybrid formulas.
+pub type Environment = HashMap<String, StateID>;
+
+///Check a formula in which the free state variables are assigned by the environment.
+///State variables that are not assigned are satisfied by no state.
+pub fn check_in<C: Colors + Debug + Clone>(
+    ctx: &mut CheckerContext<C>, formula: &Formula, env: &Environment
+) -> StateSet2<C> {
     match formula {
         &Atom(ref prop) => initial_states::<C>(&ctx.model, prop),
         &Not(ref prop) => {
             let mut result = HashMap::new();
-            let inner = check::<C>(ctx, prop);
+            let inner = check_in::<C>(ctx, prop, env);
             //println!["Inner: {:?}", inner.len()];
             for (state, colors) in initial_states::<C>(&ctx.model, &Proposition::True) {
                 if let Some(c) = inner.get(&state) {
@@ -49,8 +167,8 @@ pub fn check<C: Colors + Debug + Clone>(ctx: &mut CheckerContext<C>, formula: &F
             result
         }
         &And(ref p1, ref p2) => {
-            let p1 = check::<C>(ctx, p1);
-            let p2 = check::<C>(ctx, p2);
+            let p1 = check_in::<C>(ctx, p1, env);
+            let p2 = check_in::<C>(ctx, p2, env);
             //println!["AND: {:?} {:?}", p1, p2];
             let mut result = HashMap::new();
             for (state, colors) in p1 {
@@ -63,49 +181,84 @@ pub fn check<C: Colors + Debug + Clone>(ctx: &mut CheckerContext<C>, formula: &F
             }
             result
         }
-        &EX(ref prop) => {
-            let mut result = HashMap::new();
-            for (state, colors) in check::<C>(ctx, prop) {
-                let predecessors = compute_directed_edges::<C>(ctx, &state, &false);
-                for (predecessor, edge_colors) in predecessors {
-                    let pushed_over_edge = edge_colors.and(&colors).and(&C::model_bounds(&ctx.model));
-                    safe_add(&mut result, predecessor, pushed_over_edge);
-                }
+        &Or(ref p1, ref p2) => {
+            let mut result = check_in::<C>(ctx, p1, env);
+            for (state, colors) in check_in::<C>(ctx, p2, env) {
+                safe_add(&mut result, state, colors);
             }
             result
         }
-        &EU(ref path, ref reach) => {
-            let path = check::<C>(ctx, path);
-            //println!["Path: {:?}", path.len()];
+        //p => q is !p || q
+        &Implies(ref p1, ref p2) => check_in::<C>(ctx, &Or(Box::new(Not(p1.clone())), p2.clone()), env),
+        //AX p is !EX !p
+        &AX(ref prop) => check_in::<C>(ctx, &Not(Box::new(EX(Box::new(Not(prop.clone()))))), env),
+        &EF(ref prop) => check_in::<C>(ctx, &EU(Box::new(Atom(Proposition::True)), prop.clone()), env),
+        &AF(ref prop) => check_in::<C>(ctx, &AU(Box::new(Atom(Proposition::True)), prop.clone()), env),
+        &EG(ref prop) => {
+            //Greatest fixpoint: repeatedly remove colors of states that have no successor in the set.
             let mut result = HashMap::new();
-            let mut queue = vec![];
-            //println!["Reach: {:?}", check::<C>(model, reach)];
-            for (state, colors) in check(ctx, reach) {
-                queue.push(state.clone());
-                result.insert(state, colors);
+            for (state, colors) in check_in::<C>(ctx, prop, env) {
+                let colors = colors.and(&C::model_bounds(&ctx.model));
+                if !colors.is_empty() {
+                    result.insert(state, colors);
+                }
             }
+            let mut queue: Vec<StateID> = result.keys().cloned().collect();
+            let mut in_queue: HashSet<StateID> = queue.iter().cloned().collect();
             while let Some(state) = queue.pop() {
-                //println!["Process {:?}", result.get(&state).unwrap()];
-                let predecessors = compute_directed_edges::<C>(ctx, &state, &false);
-                for (predecessor, edge_colors) in predecessors {
-                    let pushed_over_edge = edge_colors.and(result.get(&state).unwrap());
-                    let valid_for_path = pushed_over_edge.and(
-                    path.get(&predecessor).unwrap_or(&C::ff())
-                    ).and(&C::model_bounds(&ctx.model));
-                    if safe_add(&mut result, predecessor, valid_for_path) {
+                in_queue.remove(&state);
+                let old = match result.get(&state) {
+                    Some(colors) => colors.clone(),
+                    None => continue
+                };
+                let successors = fair_successors::<C>(ctx, &state);
+                let has_successor = successors.iter().fold(C::ff(), |acc, (successor, edge_colors)| {
+                    match result.get(successor) {
+                        Some(colors) => acc.or(&edge_colors.and(colors)),
+                        None => acc
+                    }
+                });
+                let new = old.and(&has_successor);
+                if old.and(&new.not()).is_empty() {
+                    continue;
+                }
+                if new.is_empty() {
+                    result.remove(&state);
+                } else {
+                    result.insert(state, new);
+                }
+                for (predecessor, _) in compute_directed_edges::<C>(ctx, &state, &false) {
+                    if result.contains_key(&predecessor) && in_queue.insert(predecessor) {
                         queue.push(predecessor);
                     }
                 }
             }
-            //println!["Result: {:?}", result.len()];
             result
         }
+        //AG p is !EF !p
+        &AG(ref prop) => check_in::<C>(ctx, &Not(Box::new(EF(Box::new(Not(prop.clone()))))), env),
+        &EX(ref prop) => {
+            let mut result = HashMap::new();
+            for (state, colors) in check_in::<C>(ctx, prop, env) {
+                let predecessors = compute_directed_edges::<C>(ctx, &state, &false);
+                for (predecessor, edge_colors) in predecessors {
+                    let pushed_over_edge = edge_colors.and(&colors).and(&C::model_bounds(&ctx.model));
+                    safe_add(&mut result, predecessor, pushed_over_edge);
+                }
+            }
+            result
+        }
+        &EU(ref path, ref reach) => union(distances_in::<C>(ctx, path, reach, None, env)),
+        &BoundedEU(ref path, ref reach, bound) => union(distances_in::<C>(ctx, path, reach, Some(bound), env)),
+        &BoundedEF(ref prop, bound) => {
+            check_in::<C>(ctx, &BoundedEU(Box::new(Atom(Proposition::True)), prop.clone(), bound), env)
+        }
         &AU(ref path, ref reach) => {
-            let path = check::<C>(ctx, path);
+            let path = check_in::<C>(ctx, path, env);
             let mut result = HashMap::new();
             let mut queue = vec![];
             let mut uncovered: HashMap<StateID, StateSet2<C>> = HashMap::new();
-            for (state, colors) in check(ctx, reach) {
+            for (state, colors) in check_in::<C>(ctx, reach, env) {
                 queue.push(state.clone());
                 result.insert(state, colors);
             }
@@ -114,10 +267,12 @@ pub fn check<C: Colors + Debug + Clone>(ctx: &mut CheckerContext<C>, formula: &F
                 for (predecessor, edge_colors) in predecessors {
                     let pushed_over_edge = edge_colors.and(result.get(&state).unwrap());
                     let to_cover = uncovered.entry(predecessor.clone()).or_insert_with(|| {
-                        compute_directed_edges::<C>(ctx, &predecessor, &true)
+                        fair_successors::<C>(ctx, &predecessor)
                     });
-                    let new_uncovered = to_cover.get(&state).unwrap().and(&pushed_over_edge.not());
-                    to_cover.insert(state, new_uncovered);
+                    //the edge can be missing if it is an unfair self-loop
+                    if let Some(new_uncovered) = to_cover.get(&state).map(|c| c.and(&pushed_over_edge.not())) {
+                        to_cover.insert(state, new_uncovered);
+                    }
                     let total_uncovered = to_cover.values().fold(C::ff(), |acc, i| acc.or(i));
                     let valid_for_path = pushed_over_edge.and(&total_uncovered.not()).and(
                         path.get(&predecessor).unwrap_or(&C::ff())
@@ -129,10 +284,111 @@ pub fn check<C: Colors + Debug + Clone>(ctx: &mut CheckerContext<C>, formula: &F
             }
             result
         }
+        &Var(ref name) => {
+            let mut result = HashMap::new();
+            if let Some(state) = env.get(name) {
+                result.insert(*state, C::tt());
+            }
+            result
+        }
+        &Bind(ref name, ref inner) => {
+            //the inner formula is evaluated once for every state, which is bound to the variable
+            let mut result = HashMap::new();
+            let mut env = env.clone();
+            for (state, _) in initial_states::<C>(&ctx.model, &Proposition::True) {
+                env.insert(name.clone(), state);
+                if let Some(colors) = check_in::<C>(ctx, inner, &env).remove(&state) {
+                    result.insert(state, colors);
+                }
+            }
+            result
+        }
+        &At(ref name, ref inner) => {
+            let state = match env.get(name) {
+                Some(state) => *state,
+                None => return HashMap::new()
+            };
+            match check_in::<C>(ctx, inner, env).remove(&state) {
+                Some(colors) => initial_states::<C>(&ctx.model, &Proposition::True).into_iter().map(|(s, _)| {
+                    (s, colors.clone())
+                }).collect(),
+                None => HashMap::new()
+            }
+        }
+        &Exists(ref name, ref inner) => {
+            let mut result = HashMap::new();
+            let mut env = env.clone();
+            for (state, _) in initial_states::<C>(&ctx.model, &Proposition::True) {
+                env.insert(name.clone(), state);
+                for (s, colors) in check_in::<C>(ctx, inner, &env) {
+                    safe_add(&mut result, s, colors);
+                }
+            }
+            result
+        }
         //_ => HashMap::new()
     }
 }
 
+///Minimal number of transitions needed to satisfy E [path U reach]. The i-th set contains
+///the states and colors for which `reach` is reached in exactly i steps. With a bound,
+///only distances up to the bound are computed.
+pub fn distances<C: Colors + Debug + Clone>(
+    ctx: &mut CheckerContext<C>, path: &Formula, reach: &Formula, bound: Option<usize>
+) -> Vec<StateSet2<C>> {
+    distances_in::<C>(ctx, path, reach, bound, &HashMap::new())
+}
+
+fn distances_in<C: Colors + Debug + Clone>(
+    ctx: &mut CheckerContext<C>, path: &Formula, reach: &Formula, bound: Option<usize>, env: &Environment
+) -> Vec<StateSet2<C>> {
+    let bounds = C::model_bounds(&ctx.model);
+    let path: StateSet2<C> = check_in::<C>(ctx, path, env).into_iter().filter_map(|(state, colors)| {
+        let colors = colors.and(&bounds);
+        if colors.is_empty() { None } else { Some((state, colors)) }
+    }).collect();
+    let mut result = HashMap::new();
+    let mut layer = HashMap::new();
+    for (state, colors) in check_in::<C>(ctx, reach, env) {
+        if safe_add(&mut result, state, colors.clone()) {
+            layer.insert(state, colors);
+        }
+    }
+    //the EU worklist processed layer by layer, so that every color is pushed over the shortest path first
+    let mut layers = vec![];
+    while !layer.is_empty() {
+        let mut next = HashMap::new();
+        if bound.map_or(true, |bound| layers.len() < bound) {
+            for (state, colors) in &layer {
+                for (predecessor, edge_colors) in compute_directed_edges::<C>(ctx, state, &false) {
+                    let valid_for_path = edge_colors.and(colors).and(path.get(&predecessor).unwrap_or(&C::ff()));
+                    let new_colors = match result.get(&predecessor) {
+                        Some(old) => valid_for_path.and(&old.not()),
+                        None => valid_for_path
+                    };
+                    safe_add(&mut next, predecessor, new_colors);
+                }
+            }
+        }
+        for (state, colors) in &next {
+            safe_add(&mut result, *state, colors.clone());
+        }
+        layers.push(layer);
+        layer = next;
+    }
+    layers
+}
+
+fn union<C: Colors + Debug>(layers: Vec<StateSet2<C>>) -> StateSet2<C> {
+    let mut result = HashMap::new();
+    for layer in layers {
+        for (state, colors) in layer {
+            safe_add(&mut result, state, colors);
+        }
+    }
+    result
+}
+
 //Performs a union on old and new color sets.
 //Returns true if the value for the key changes.
 fn safe_add<C: Colors + Debug>(set: &mut StateSet2<C>, key: StateID, value: C) -> bool {
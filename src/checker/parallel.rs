@@ -0,0 +1,426 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::channel;
+use std::thread;
+
+use super::safe_add;
+use super::fair_edges;
+use super::CheckError;
+use super::Fairness;
+use super::super::formula::Formula;
+use super::super::formula::Formula::*;
+use super::super::formula::Proposition;
+use super::super::generator::StateSet2;
+use super::super::generator::initial_states;
+use super::super::generator::directed_edges;
+use super::super::model::Model;
+use super::super::model::CompileError;
+use super::super::model::StateID;
+use super::super::model::TimeFlow;
+use super::super::parameters::Colors;
+
+// The parallel checker evaluates fixpoints in bulk synchronous rounds. Every state is owned
+// by worker `state % threads`, which stores its part of the result (and of the AU uncovered map
+// or the EG successor map).
+// The workers of a fixpoint are started once and driven by commands sent over channels.
+// In each round, workers first expand their frontier states (computing predecessors is the
+// expensive part) and then merge the colors pushed to the states they own. Pushed colors are
+// merged in the order of (predecessor, state), so the result does not depend on thread scheduling
+// and contains the same colors as the result of the sequential `checker::check`.
+//
+// Only colors that can be shared between threads are supported (Order0, Order1). OrderN is not
+// supported: it uses the global Z3 context `order_n::Z3`, which is not thread safe.
+// Hybrid operators are rejected with an error. The fairness mode of the context is applied
+// to EG and AU the same way as in the sequential checker.
+
+/// Edge cache that can be shared by multiple threads. Each shard is locked separately.
+pub struct EdgeCache<C: Colors> {
+    shards: Vec<Mutex<HashMap<(StateID, TimeFlow), StateSet2<C>>>>
+}
+
+impl<C: Colors + Debug + Clone> EdgeCache<C> {
+    pub fn new(shards: usize) -> EdgeCache<C> {
+        EdgeCache { shards: (0..shards.max(1)).map(|_| Mutex::new(HashMap::new())).collect() }
+    }
+
+    pub fn edges(&self, model: &Model, from: &StateID, time_flow: &TimeFlow) -> StateSet2<C> {
+        let ref shard = self.shards[from % self.shards.len()];
+        if let Some(edges) = shard.lock().unwrap().get(&(*from, *time_flow)) {
+            return edges.clone();
+        }
+        //compute without holding the lock, the result is the same if two threads race
+        let edges = directed_edges::<C>(model, from, time_flow)
+            .expect("Colors do not support the model of the parallel context");
+        shard.lock().unwrap().insert((*from, *time_flow), edges.clone());
+        edges
+    }
+}
+
+pub struct ParallelContext<C: Colors> {
+    pub model: Arc<Model>,
+    pub threads: usize,
+    pub fairness: Fairness,
+    pub edge_cache: Arc<EdgeCache<C>>
+}
+
+impl<C: Colors + Debug + Clone> ParallelContext<C> {
+    /// Fails if the colors can't represent the parameter constraints of the model.
+    pub fn new(model: Model, threads: usize) -> Result<ParallelContext<C>, CompileError> {
+        try!(C::supports(&model));
+        let threads = threads.max(1);
+        Ok(ParallelContext {
+            model: Arc::new(model),
+            threads: threads,
+            fairness: Fairness::Disabled,
+            edge_cache: Arc::new(EdgeCache::new(threads * 16))
+        })
+    }
+}
+
+/// Parallel version of `checker::check`. Fails on operators the parallel checker does not support.
+pub fn check_parallel<C>(ctx: &ParallelContext<C>, formula: &Formula) -> Result<StateSet2<C>, CheckError>
+    where C: Colors + Debug + Clone + Send + Sync + 'static
+{
+    let tt = || Box::new(Atom(Proposition::True));
+    match formula {
+        &Atom(ref prop) => Ok(initial_states::<C>(&ctx.model, prop)),
+        &Not(ref inner) => {
+            let inner = try!(check_parallel(ctx, inner));
+            let bounds = C::model_bounds(&ctx.model);
+            let mut result = HashMap::new();
+            for (state, colors) in initial_states::<C>(&ctx.model, &Proposition::True) {
+                let colors = match inner.get(&state) {
+                    Some(c) => colors.and(&c.not()).and(&bounds),
+                    None => colors
+                };
+                if !colors.is_empty() {
+                    result.insert(state, colors);
+                }
+            }
+            Ok(result)
+        }
+        &And(ref left, ref right) => {
+            let left = try!(check_parallel(ctx, left));
+            let right = try!(check_parallel(ctx, right));
+            let bounds = C::model_bounds(&ctx.model);
+            let mut result = HashMap::new();
+            for (state, colors) in left {
+                if let Some(c) = right.get(&state) {
+                    let colors = colors.and(c).and(&bounds);
+                    if !colors.is_empty() {
+                        result.insert(state, colors);
+                    }
+                }
+            }
+            Ok(result)
+        }
+        &Or(ref left, ref right) => {
+            let mut result = try!(check_parallel(ctx, left));
+            for (state, colors) in try!(check_parallel(ctx, right)) {
+                safe_add(&mut result, state, colors);
+            }
+            Ok(result)
+        }
+        &Implies(ref left, ref right) => check_parallel(ctx, &Or(Box::new(Not(left.clone())), right.clone())),
+        &EX(ref inner) => {
+            let inner = try!(check_parallel(ctx, inner));
+            let bounds = C::model_bounds(&ctx.model);
+            let frontier = partition(ctx.threads, inner.keys().cloned());
+            let inboxes = expand(ctx, partition_set(ctx.threads, inner), frontier);
+            let mut result = HashMap::new();
+            for inbox in inboxes {
+                for (predecessor, _, pushed) in inbox {
+                    safe_add(&mut result, predecessor, pushed.and(&bounds));
+                }
+            }
+            Ok(result)
+        }
+        &AX(ref inner) => check_parallel(ctx, &Not(Box::new(EX(Box::new(Not(inner.clone())))))),
+        &EF(ref inner) => check_parallel(ctx, &EU(tt(), inner.clone())),
+        &AF(ref inner) => check_parallel(ctx, &AU(tt(), inner.clone())),
+        &EG(ref inner) => {
+            let bounds = C::model_bounds(&ctx.model);
+            let mut initial = HashMap::new();
+            for (state, colors) in try!(check_parallel(ctx, inner)) {
+                let colors = colors.and(&bounds);
+                if !colors.is_empty() {
+                    initial.insert(state, colors);
+                }
+            }
+            Ok(fixpoint(ctx, Operator::Globally, HashMap::new(), initial, None))
+        }
+        &AG(ref inner) => check_parallel(ctx, &Not(Box::new(EF(Box::new(Not(inner.clone())))))),
+        &EU(ref path, ref reach) => {
+            let path = try!(check_parallel(ctx, path));
+            let reach = try!(check_parallel(ctx, reach));
+            Ok(fixpoint(ctx, Operator::Until { all: false }, path, reach, None))
+        }
+        &AU(ref path, ref reach) => {
+            let path = try!(check_parallel(ctx, path));
+            let reach = try!(check_parallel(ctx, reach));
+            Ok(fixpoint(ctx, Operator::Until { all: true }, path, reach, None))
+        }
+        &BoundedEF(ref inner, bound) => check_parallel(ctx, &BoundedEU(tt(), inner.clone(), bound)),
+        &BoundedEU(ref path, ref reach, bound) => {
+            let path = try!(check_parallel(ctx, path));
+            let reach = try!(check_parallel(ctx, reach));
+            Ok(fixpoint(ctx, Operator::Until { all: false }, path, reach, Some(bound)))
+        }
+        &Var(_) | &Bind(..) | &At(..) | &Exists(..) => Err(CheckError::UnsupportedOperator(formula.clone()))
+    }
+}
+
+fn owner(state: &StateID, threads: usize) -> usize {
+    state % threads
+}
+
+//Split states by owner, each part sorted.
+fn partition<I: Iterator<Item=StateID>>(threads: usize, states: I) -> Vec<Vec<StateID>> {
+    let mut result = vec![vec![]; threads];
+    for state in states {
+        result[owner(&state, threads)].push(state);
+    }
+    for part in &mut result {
+        part.sort();
+    }
+    result
+}
+
+fn partition_set<C: Colors>(threads: usize, set: StateSet2<C>) -> Vec<StateSet2<C>> {
+    let mut result: Vec<StateSet2<C>> = (0..threads).map(|_| HashMap::new()).collect();
+    for (state, colors) in set {
+        result[owner(&state, threads)].insert(state, colors);
+    }
+    result
+}
+
+//Pushed colors: (predecessor, state, colors of the edge and the state)
+type Inbox<C> = Vec<(StateID, StateID, C)>;
+
+//Push the colors of the frontier states to their predecessors. Returns an outbox for every owner.
+fn push<C: Colors + Debug + Clone>(
+    model: &Model, cache: &EdgeCache<C>, set: &StateSet2<C>, frontier: &[StateID], threads: usize
+) -> Vec<Inbox<C>> {
+    let mut outboxes: Vec<Inbox<C>> = (0..threads).map(|_| vec![]).collect();
+    for state in frontier {
+        for (predecessor, edge_colors) in cache.edges(model, state, &false) {
+            let pushed = edge_colors.and(&set[state]);
+            if !pushed.is_empty() {
+                outboxes[owner(&predecessor, threads)].push((predecessor, *state, pushed));
+            }
+        }
+    }
+    outboxes
+}
+
+//Deliver the outboxes of all workers to the owners, sorted by (predecessor, state).
+fn deliver<C>(threads: usize, outboxes: Vec<Vec<Inbox<C>>>) -> Vec<Inbox<C>> {
+    let mut inboxes: Vec<Inbox<C>> = (0..threads).map(|_| vec![]).collect();
+    for worker_outboxes in outboxes {
+        for (inbox, outbox) in inboxes.iter_mut().zip(worker_outboxes.into_iter()) {
+            inbox.extend(outbox);
+        }
+    }
+    for inbox in &mut inboxes {
+        inbox.sort_by(|a, b| (a.0, a.1).cmp(&(b.0, b.1)));
+    }
+    inboxes
+}
+
+//A single round of pushing colors (used by EX).
+fn expand<C>(ctx: &ParallelContext<C>, partitions: Vec<StateSet2<C>>, frontier: Vec<Vec<StateID>>) -> Vec<Inbox<C>>
+    where C: Colors + Debug + Clone + Send + Sync + 'static
+{
+    let threads = ctx.threads;
+    let workers: Vec<_> = partitions.into_iter().zip(frontier.into_iter()).map(|(part, states)| {
+        let model = ctx.model.clone();
+        let cache = ctx.edge_cache.clone();
+        thread::spawn(move || push(&model, &cache, &part, &states, threads))
+    }).collect();
+    deliver(threads, workers.into_iter().map(|worker| worker.join().unwrap()).collect())
+}
+
+//Commands sent to the workers of `fixpoint` in every round.
+enum Command<C> {
+    //First phase: push colors of the frontier to predecessors and send back the outboxes.
+    Expand,
+    //Second phase: merge pushed colors into the owned states and report whether any changed.
+    Merge(Inbox<C>),
+    //Fixpoint is reached, return the owned part of the result.
+    Finish
+}
+
+//Fixpoints evaluated by `fixpoint`.
+#[derive(Clone, Copy, PartialEq)]
+enum Operator {
+    //Least fixpoint of EU (all = false) or AU (all = true), starting from the reach set.
+    Until { all: bool },
+    //Greatest fixpoint of EG, starting from the states of the inner formula.
+    Globally
+}
+
+//Colors added to EU/AU in round i need exactly i transitions, so a bound limits the number
+//of rounds. Workers are spawned once and kept in lockstep by the commands.
+fn fixpoint<C>(ctx: &ParallelContext<C>, operator: Operator, path: StateSet2<C>, initial: StateSet2<C>,
+    bound: Option<usize>) -> StateSet2<C>
+    where C: Colors + Debug + Clone + Send + Sync + 'static
+{
+    let threads = ctx.threads;
+    let fairness = ctx.fairness;
+    let path = Arc::new(path);
+    let mut active = !initial.is_empty();
+    let frontier = partition(threads, initial.keys().cloned());
+    let mut commands = vec![];
+    let mut pushed_receivers = vec![];
+    let mut changed_receivers = vec![];
+    let mut workers = vec![];
+    for (part, states) in partition_set(threads, initial).into_iter().zip(frontier.into_iter()) {
+        let (command_sender, command_receiver) = channel::<Command<C>>();
+        let (pushed_sender, pushed_receiver) = channel();
+        let (changed_sender, changed_receiver) = channel();
+        let model = ctx.model.clone();
+        let cache = ctx.edge_cache.clone();
+        let path = path.clone();
+        workers.push(thread::spawn(move || {
+            let bounds = C::model_bounds(&model);
+            let mut part = part;
+            let mut frontier = states;
+            //EG pushes the whole set in the first round and then only the removed colors
+            let mut removed = if operator == Operator::Globally { part.clone() } else { HashMap::new() };
+            let mut first = true;
+            //AU: colors of successors that are not covered yet, EG: colors of successors in the set
+            let mut successors: HashMap<StateID, StateSet2<C>> = HashMap::new();
+            loop {
+                match command_receiver.recv().unwrap() {
+                    Command::Expand => {
+                        let source = if operator == Operator::Globally { &removed } else { &part };
+                        pushed_sender.send(push(&model, &cache, source, &frontier, threads)).unwrap()
+                    }
+                    Command::Merge(inbox) => {
+                        frontier = match operator {
+                            Operator::Until { all } => merge_until(&model, &cache, fairness, &path, &bounds, all,
+                                &mut part, &mut successors, inbox),
+                            Operator::Globally => merge_globally(&model, &cache, fairness, first,
+                                &mut part, &mut successors, &mut removed, inbox)
+                        };
+                        first = false;
+                        changed_sender.send(!frontier.is_empty()).unwrap();
+                    }
+                    Command::Finish => return part
+                }
+            }
+        }));
+        commands.push(command_sender);
+        pushed_receivers.push(pushed_receiver);
+        changed_receivers.push(changed_receiver);
+    }
+    let mut rounds = 0;
+    while active && bound.map_or(true, |b| rounds < b) {
+        rounds += 1;
+        for command in &commands {
+            command.send(Command::Expand).unwrap();
+        }
+        let inboxes = deliver(threads, pushed_receivers.iter().map(|receiver| receiver.recv().unwrap()).collect());
+        for (command, inbox) in commands.iter().zip(inboxes.into_iter()) {
+            command.send(Command::Merge(inbox)).unwrap();
+        }
+        //every worker has to answer, so the replies are collected before they are combined
+        let replies: Vec<bool> = changed_receivers.iter().map(|receiver| receiver.recv().unwrap()).collect();
+        active = replies.into_iter().any(|c| c);
+    }
+    for command in &commands {
+        command.send(Command::Finish).unwrap();
+    }
+    let mut result = HashMap::new();
+    for worker in workers {
+        result.extend(worker.join().unwrap());
+    }
+    result
+}
+
+//Add pushed colors that satisfy the path (and for AU cover all fair successors) to the owned states.
+//Returns the sorted states that changed.
+fn merge_until<C: Colors + Debug + Clone>(
+    model: &Model, cache: &EdgeCache<C>, fairness: Fairness, path: &StateSet2<C>, bounds: &C, all: bool,
+    part: &mut StateSet2<C>, to_cover: &mut HashMap<StateID, StateSet2<C>>, inbox: Inbox<C>
+) -> Vec<StateID> {
+    let mut changed = HashSet::new();
+    for (predecessor, state, pushed) in inbox {
+        let mut valid = pushed.and(path.get(&predecessor).unwrap_or(&C::ff())).and(bounds);
+        if all {
+            let successors = to_cover.entry(predecessor).or_insert_with(|| {
+                fair_edges(&predecessor, cache.edges(model, &predecessor, &true), fairness)
+            });
+            //the edge can be missing if it is an unfair self-loop
+            if let Some(new_uncovered) = successors.get(&state).map(|c| c.and(&pushed.not())) {
+                successors.insert(state, new_uncovered);
+            }
+            let total_uncovered = successors.values().fold(C::ff(), |acc, i| acc.or(i));
+            valid = valid.and(&total_uncovered.not());
+        }
+        if safe_add(part, predecessor, valid) {
+            changed.insert(predecessor);
+        }
+    }
+    let mut changed: Vec<StateID> = changed.into_iter().collect();
+    changed.sort();
+    changed
+}
+
+//Track the colors of successors that are still in the EG set and remove the colors of owned states
+//that have no such successor. In the first round every owned state is checked and the pushed colors
+//are the whole set, later only the removed colors are pushed. Returns the sorted states that changed,
+//their removed colors are stored in `removed`.
+fn merge_globally<C: Colors + Debug + Clone>(
+    model: &Model, cache: &EdgeCache<C>, fairness: Fairness, first: bool,
+    part: &mut StateSet2<C>, in_set: &mut HashMap<StateID, StateSet2<C>>, removed: &mut StateSet2<C>, inbox: Inbox<C>
+) -> Vec<StateID> {
+    let mut touched: HashSet<StateID> = if first { part.keys().cloned().collect() } else { HashSet::new() };
+    for (predecessor, state, pushed) in inbox {
+        if !part.contains_key(&predecessor) {
+            continue;
+        }
+        let successors = in_set.entry(predecessor).or_insert_with(HashMap::new);
+        let colors = if first {
+            if predecessor == state {
+                match fair_edges(&predecessor, cache.edges(model, &predecessor, &true), fairness).remove(&state) {
+                    Some(fair) => pushed.and(&fair),
+                    None => continue
+                }
+            } else {
+                pushed
+            }
+        } else {
+            match successors.get(&state) {
+                Some(colors) => colors.and(&pushed.not()),
+                None => continue
+            }
+        };
+        successors.insert(state, colors);
+        touched.insert(predecessor);
+    }
+    removed.clear();
+    let mut changed = vec![];
+    for state in touched {
+        let old = part[&state].clone();
+        let has_successor = in_set.get(&state).map_or(C::ff(), |successors| {
+            successors.values().fold(C::ff(), |acc, c| acc.or(c))
+        });
+        let lost = old.and(&has_successor.not());
+        if lost.is_empty() {
+            continue;
+        }
+        let new = old.and(&has_successor);
+        if new.is_empty() {
+            part.remove(&state);
+        } else {
+            part.insert(state, new);
+        }
+        removed.insert(state, lost);
+        changed.push(state);
+    }
+    changed.sort();
+    changed
+}
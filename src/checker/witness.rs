@@ -0,0 +1,193 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
+
+use rustc_serialize::json::{ToJson, Json};
+use json_utils::{JsonMap, create_object};
+
+use super::check;
+use super::fair_successors;
+use super::CheckerContext;
+use super::super::formula::Formula;
+use super::super::formula::Formula::*;
+use super::super::formula::Proposition;
+use super::super::generator::StateSet2;
+use super::super::generator::compute_directed_edges;
+use super::super::model::Model;
+use super::super::model::StateID;
+use super::super::parameters::Colors;
+
+/// A path in the state transition graph that explains why a state satisfies a formula.
+#[derive(Debug, Clone)]
+pub struct Witness<C: Colors> {
+    /// States of the path, starting with the explained state.
+    pub states: Vec<StateID>,
+    /// Colors for which every transition of the path exists. This is a non-empty
+    /// subset of the colors the witness was requested for.
+    pub colors: C,
+    /// For infinite (lasso) witnesses, index of the state the last state loops back to.
+    pub loop_start: Option<usize>
+}
+
+impl<C: Colors> Witness<C> {
+    /// Export the path with rectangle bounds of each state.
+    pub fn export(&self, model: &Model) -> Json {
+        create_object(|map| {
+            map.write_item("states", &self.states.iter().map(|state| {
+                create_object(|map| {
+                    map.write_item("id", state);
+                    map.write_item("bounds", &model.expand_state(state));
+                })
+            }).collect::<Vec<Json>>());
+            map.write_item("loopStart", &self.loop_start.map(|i| i.to_json()).unwrap_or(Json::Null));
+        })
+    }
+}
+
+/// Find a path starting in `state` showing that the formula holds for some of the given colors.
+/// Supported formulas are EX, EF, EU and EG. Returns None if the formula is not supported
+/// or does not hold in the state for any of the colors.
+pub fn witness<C: Colors + Debug + Clone>(
+    ctx: &mut CheckerContext<C>, formula: &Formula, state: &StateID, colors: &C
+) -> Option<Witness<C>> {
+    match formula {
+        &EX(ref inner) => {
+            let target = check::<C>(ctx, inner);
+            next_witness(ctx, &target, state, colors)
+        }
+        &EF(ref reach) => {
+            let path = check::<C>(ctx, &Atom(Proposition::True));
+            let reach = check::<C>(ctx, reach);
+            reach_witness(ctx, &path, &reach, state, colors)
+        }
+        &EU(ref path, ref reach) => {
+            let path = check::<C>(ctx, path);
+            let reach = check::<C>(ctx, reach);
+            reach_witness(ctx, &path, &reach, state, colors)
+        }
+        &EG(_) => {
+            let set = check::<C>(ctx, formula);
+            lasso_witness(ctx, &set, state, colors)
+        }
+        _ => None
+    }
+}
+
+/// Find a path starting in `state` showing that the formula is violated for some of the given colors.
+/// Supported formulas are AX, AF and AG, the counterexample is a witness of EX !p, EG !p
+/// and EF !p respectively.
+pub fn counterexample<C: Colors + Debug + Clone>(
+    ctx: &mut CheckerContext<C>, formula: &Formula, state: &StateID, colors: &C
+) -> Option<Witness<C>> {
+    match formula {
+        &AX(ref inner) => witness(ctx, &EX(Box::new(Not(inner.clone()))), state, colors),
+        &AF(ref inner) => witness(ctx, &EG(Box::new(Not(inner.clone()))), state, colors),
+        &AG(ref inner) => witness(ctx, &EF(Box::new(Not(inner.clone()))), state, colors),
+        _ => None
+    }
+}
+
+fn next_witness<C: Colors + Debug + Clone>(
+    ctx: &mut CheckerContext<C>, target: &StateSet2<C>, state: &StateID, colors: &C
+) -> Option<Witness<C>> {
+    for (successor, edge_colors) in compute_directed_edges::<C>(ctx, state, &true) {
+        if let Some(target_colors) = target.get(&successor) {
+            let valid = colors.and(&edge_colors).and(target_colors);
+            if !valid.is_empty() {
+                return Some(Witness { states: vec![*state, successor], colors: valid, loop_start: None });
+            }
+        }
+    }
+    None
+}
+
+//Breadth first search, so the witness is one of the shortest paths.
+//Each state is expanded again only for colors it was not reached with before.
+fn reach_witness<C: Colors + Debug + Clone>(
+    ctx: &mut CheckerContext<C>, path: &StateSet2<C>, reach: &StateSet2<C>, state: &StateID, colors: &C
+) -> Option<Witness<C>> {
+    //(state, colors, index of the previous node)
+    let mut nodes: Vec<(StateID, C, Option<usize>)> = vec![(*state, colors.clone(), None)];
+    let mut seen: StateSet2<C> = HashMap::new();
+    seen.insert(*state, colors.clone());
+    let mut next = 0;
+    while next < nodes.len() {
+        let (current, current_colors) = (nodes[next].0, nodes[next].1.clone());
+        if let Some(reach_colors) = reach.get(&current) {
+            let found = current_colors.and(reach_colors);
+            if !found.is_empty() {
+                let mut states = vec![];
+                let mut node = Some(next);
+                while let Some(i) = node {
+                    states.push(nodes[i].0);
+                    node = nodes[i].2;
+                }
+                states.reverse();
+                return Some(Witness { states: states, colors: found, loop_start: None });
+            }
+        }
+        if let Some(path_colors) = path.get(&current) {
+            let current_colors = current_colors.and(path_colors);
+            for (successor, edge_colors) in compute_directed_edges::<C>(ctx, &current, &true) {
+                let pushed = current_colors.and(&edge_colors);
+                let new = match seen.get(&successor) {
+                    Some(old) => pushed.and(&old.not()),
+                    None => pushed
+                };
+                if !new.is_empty() {
+                    let union = match seen.get(&successor) {
+                        Some(old) => old.or(&new),
+                        None => new.clone()
+                    };
+                    seen.insert(successor, union);
+                    nodes.push((successor, new, Some(next)));
+                }
+            }
+        }
+        next += 1;
+    }
+    None
+}
+
+//Every color of a state in the EG set has a successor in the set, so we can always
+//continue until some state repeats.
+fn lasso_witness<C: Colors + Debug + Clone>(
+    ctx: &mut CheckerContext<C>, set: &StateSet2<C>, state: &StateID, colors: &C
+) -> Option<Witness<C>> {
+    let mut colors = match set.get(state) {
+        Some(valid) => colors.and(valid),
+        None => return None
+    };
+    if colors.is_empty() {
+        return None;
+    }
+    let mut states = vec![*state];
+    loop {
+        let current = *states.last().unwrap();
+        let mut next = None;
+        for (successor, edge_colors) in fair_successors::<C>(ctx, &current) {
+            if let Some(valid) = set.get(&successor) {
+                let pushed = colors.and(&edge_colors).and(valid);
+                if !pushed.is_empty() {
+                    //prefer closing the loop
+                    let closes = states.contains(&successor);
+                    if closes || next.is_none() {
+                        next = Some((successor, pushed));
+                    }
+                    if closes {
+                        break;
+                    }
+                }
+            }
+        }
+        match next {
+            Some((successor, pushed)) => {
+                colors = pushed;
+                if let Some(i) = states.iter().position(|s| *s == successor) {
+                    return Some(Witness { states: states, colors: colors, loop_start: Some(i) });
+                }
+                states.push(successor);
+            }
+            None => return None
+        }
+    }
+}
@@ -6,26 +6,168 @@ use json_utils::{FromJson, JsonMap, as_object};
 
 use super::model::VariableIndex;
 use super::model::ThresholdIndex;
+use super::model::ParameterIndex;
+use super::OdeModel;
 
-#[derive(Debug)]
+pub mod parser;
+
+#[derive(Debug, PartialEq, Clone)]
 pub enum CompareOp {
-    LT, GT
+    LT, GT, LE, GE
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum Proposition {
     True, False,
-    Equation(VariableIndex, CompareOp, ThresholdIndex)
+    Equation(VariableIndex, CompareOp, ThresholdIndex),
+    ///Comparison of a variable with a real value. Unless the value is a threshold of the variable
+    ///(see `OdeModel::refine_thresholds`), only states that lie entirely on the valid side are included.
+    Value(VariableIndex, CompareOp, f64),
+    ///low < variable < high
+    Range(VariableIndex, f64, f64),
+    ///sum of coefficient * variable compared with a constant, evaluated on rectangle vertices.
+    Linear(Vec<(VariableIndex, f64)>, CompareOp, f64, VertexSemantics),
+    ///sum of coefficient * parameter compared with a constant. Holds in all states
+    ///for the colors that satisfy the constraint.
+    Parameters(Vec<(ParameterIndex, f64)>, CompareOp, f64)
+}
+
+///Decides when a rectangle satisfies a proposition that can be partially true on it.
+#[derive(Debug, PartialEq, Clone)]
+pub enum VertexSemantics {
+    ///All vertices of the rectangle satisfy the proposition.
+    AllVertices,
+    ///At least one vertex of the rectangle satisfies the proposition.
+    SomeVertex
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum Formula {
     Atom(Proposition),
     Not(Box<Formula>),
     And(Box<Formula>, Box<Formula>),
+    Or(Box<Formula>, Box<Formula>),
+    Implies(Box<Formula>, Box<Formula>),
     EX(Box<Formula>),
+    AX(Box<Formula>),
+    EF(Box<Formula>),
+    AF(Box<Formula>),
+    EG(Box<Formula>),
+    AG(Box<Formula>),
     EU(Box<Formula>, Box<Formula>),
-    AU(Box<Formula>, Box<Formula>)
+    AU(Box<Formula>, Box<Formula>),
+    ///EF with at most the given number of transitions.
+    BoundedEF(Box<Formula>, usize),
+    ///EU with at most the given number of transitions.
+    BoundedEU(Box<Formula>, Box<Formula>, usize),
+    ///Holds exactly in the state assigned to the state variable.
+    Var(String),
+    ///Assign the current state to the state variable (binder).
+    Bind(String, Box<Formula>),
+    ///Evaluate the formula in the state assigned to the state variable.
+    At(String, Box<Formula>),
+    ///Holds if the formula holds for some assignment of the state variable.
+    Exists(String, Box<Formula>)
+}
+
+impl Formula {
+    ///Replace propositions with threshold indices by equivalent propositions with real values
+    ///taken from the model. The result stays valid when thresholds are added to the model.
+    pub fn with_values(&self, model: &OdeModel) -> Formula {
+        self.map_propositions(&|prop| match prop {
+            &Proposition::Equation(variable, ref op, threshold) => {
+                match model.variables.get(variable).and_then(|v| v.thresholds.get(threshold)) {
+                    Some(value) => Proposition::Value(variable, op.clone(), *value),
+                    None => prop.clone()
+                }
+            }
+            _ => prop.clone()
+        })
+    }
+
+    fn map_propositions(&self, f: &Fn(&Proposition) -> Proposition) -> Formula {
+        let map = |inner: &Box<Formula>| Box::new(inner.map_propositions(f));
+        match self {
+            &Formula::Atom(ref prop) => Formula::Atom(f(prop)),
+            &Formula::Not(ref inner) => Formula::Not(map(inner)),
+            &Formula::EX(ref inner) => Formula::EX(map(inner)),
+            &Formula::AX(ref inner) => Formula::AX(map(inner)),
+            &Formula::EF(ref inner) => Formula::EF(map(inner)),
+            &Formula::AF(ref inner) => Formula::AF(map(inner)),
+            &Formula::EG(ref inner) => Formula::EG(map(inner)),
+            &Formula::AG(ref inner) => Formula::AG(map(inner)),
+            &Formula::And(ref left, ref right) => Formula::And(map(left), map(right)),
+            &Formula::Or(ref left, ref right) => Formula::Or(map(left), map(right)),
+            &Formula::Implies(ref left, ref right) => Formula::Implies(map(left), map(right)),
+            &Formula::EU(ref left, ref right) => Formula::EU(map(left), map(right)),
+            &Formula::AU(ref left, ref right) => Formula::AU(map(left), map(right)),
+            &Formula::BoundedEF(ref inner, bound) => Formula::BoundedEF(map(inner), bound),
+            &Formula::BoundedEU(ref left, ref right, bound) => Formula::BoundedEU(map(left), map(right), bound),
+            &Formula::Var(ref name) => Formula::Var(name.clone()),
+            &Formula::Bind(ref name, ref inner) => Formula::Bind(name.clone(), map(inner)),
+            &Formula::At(ref name, ref inner) => Formula::At(name.clone(), map(inner)),
+            &Formula::Exists(ref name, ref inner) => Formula::Exists(name.clone(), map(inner))
+        }
+    }
+
+    ///State variables that are used outside of a binder of the same name.
+    pub fn free_variables(&self) -> Vec<String> {
+        let mut result = vec![];
+        self.collect_free_variables(&mut vec![], &mut result);
+        result
+    }
+
+    ///True if the formula contains state variables or hybrid operators.
+    pub fn is_hybrid(&self) -> bool {
+        match self {
+            &Formula::Var(_) | &Formula::Bind(..) | &Formula::At(..) | &Formula::Exists(..) => true,
+            _ => self.children().iter().any(|f| f.is_hybrid())
+        }
+    }
+
+    fn collect_free_variables(&self, bound: &mut Vec<String>, result: &mut Vec<String>) {
+        match self {
+            &Formula::Var(ref name) | &Formula::At(ref name, _) => {
+                if !bound.contains(name) && !result.contains(name) {
+                    result.push(name.clone());
+                }
+            }
+            _ => {}
+        }
+        match self {
+            &Formula::Bind(ref name, ref inner) | &Formula::Exists(ref name, ref inner) => {
+                bound.push(name.clone());
+                inner.collect_free_variables(bound, result);
+                bound.pop();
+            }
+            _ => for child in self.children() {
+                child.collect_free_variables(bound, result);
+            }
+        }
+    }
+
+    fn children(&self) -> Vec<&Formula> {
+        match self {
+            &Formula::Atom(_) | &Formula::Var(_) => vec![],
+            &Formula::Not(ref inner) | &Formula::EX(ref inner) | &Formula::AX(ref inner) |
+            &Formula::EF(ref inner) | &Formula::AF(ref inner) |
+            &Formula::EG(ref inner) | &Formula::AG(ref inner) |
+            &Formula::BoundedEF(ref inner, _) | &Formula::Bind(_, ref inner) |
+            &Formula::At(_, ref inner) | &Formula::Exists(_, ref inner) => vec![&**inner],
+            &Formula::And(ref left, ref right) | &Formula::Or(ref left, ref right) |
+            &Formula::Implies(ref left, ref right) |
+            &Formula::EU(ref left, ref right) | &Formula::AU(ref left, ref right) |
+            &Formula::BoundedEU(ref left, ref right, _) => vec![&**left, &**right]
+        }
+    }
+
+    ///All atomic propositions of the formula.
+    pub fn propositions(&self) -> Vec<&Proposition> {
+        match self {
+            &Formula::Atom(ref prop) => vec![prop],
+            _ => self.children().into_iter().flat_map(|f| f.propositions()).collect()
+        }
+    }
 }
 
 impl FromJson<CompareOp> for CompareOp {
@@ -34,6 +176,8 @@ impl FromJson<CompareOp> for CompareOp {
             match str {
                 "LT" => Ok(CompareOp::LT),
                 "GT" => Ok(CompareOp::GT),
+                "LE" => Ok(CompareOp::LE),
+                "GE" => Ok(CompareOp::GE),
                 _ => Err(DecoderError::UnknownVariantError("Unknown comparison operator".to_string()))
             }
         } else {
@@ -42,6 +186,20 @@ impl FromJson<CompareOp> for CompareOp {
     }
 }
 
+impl FromJson<VertexSemantics> for VertexSemantics {
+    fn from_json(json: &Json) -> Result<VertexSemantics, DecoderError> {
+        if let Some(str) = json.as_string() {
+            match str {
+                "All" => Ok(VertexSemantics::AllVertices),
+                "Some" => Ok(VertexSemantics::SomeVertex),
+                _ => Err(DecoderError::UnknownVariantError("Unknown vertex semantics".to_string()))
+            }
+        } else {
+            Err(DecoderError::ExpectedError("String".to_string(), json.to_string()))
+        }
+    }
+}
+
 impl FromJson<Proposition> for Proposition {
     fn from_json(json: &Json) -> Result<Proposition, DecoderError> {
         if let Some(str) = json.as_string() {
@@ -52,11 +210,52 @@ impl FromJson<Proposition> for Proposition {
             }
         } else {
             as_object(json, |map| {
-                Ok(Proposition::Equation(
-                    try![map.read_item::<u64>("variableIndex")] as usize,
-                    try![map.read_item::<CompareOp>("compareOp")],
-                    try![map.read_item::<u64>("thresholdIndex")] as usize
-                ))
+                if json.find("parameterIndices").is_some() {
+                    let parameters = try![map.read_item::<Vec<u64>>("parameterIndices")];
+                    let coefficients = try![map.read_item::<Vec<f64>>("coefficients")];
+                    if parameters.len() != coefficients.len() {
+                        return Err(DecoderError::ApplicationError(
+                            "Parameter indices and coefficients differ in length".to_string()
+                        ));
+                    }
+                    return Ok(Proposition::Parameters(
+                        parameters.into_iter().map(|p| p as usize).zip(coefficients.into_iter()).collect(),
+                        try![map.read_item::<CompareOp>("compareOp")],
+                        try![map.read_item::<f64>("value")]
+                    ));
+                }
+                if json.find("coefficients").is_some() {
+                    let variables = try![map.read_item::<Vec<u64>>("variableIndices")];
+                    let coefficients = try![map.read_item::<Vec<f64>>("coefficients")];
+                    if variables.len() != coefficients.len() {
+                        return Err(DecoderError::ApplicationError(
+                            "Variable indices and coefficients differ in length".to_string()
+                        ));
+                    }
+                    let semantics = try![map.read_optional_item::<VertexSemantics>("semantics")];
+                    return Ok(Proposition::Linear(
+                        variables.into_iter().map(|v| v as usize).zip(coefficients.into_iter()).collect(),
+                        try![map.read_item::<CompareOp>("compareOp")],
+                        try![map.read_item::<f64>("value")],
+                        semantics.unwrap_or(VertexSemantics::AllVertices)
+                    ));
+                }
+                let variable = try![map.read_item::<u64>("variableIndex")] as usize;
+                if json.find("low").is_some() {
+                    Ok(Proposition::Range(variable, try![map.read_item::<f64>("low")], try![map.read_item::<f64>("high")]))
+                } else if json.find("value").is_some() {
+                    Ok(Proposition::Value(
+                        variable,
+                        try![map.read_item::<CompareOp>("compareOp")],
+                        try![map.read_item::<f64>("value")]
+                    ))
+                } else {
+                    Ok(Proposition::Equation(
+                        variable,
+                        try![map.read_item::<CompareOp>("compareOp")],
+                        try![map.read_item::<u64>("thresholdIndex")] as usize
+                    ))
+                }
             })
         }
     }
@@ -70,10 +269,23 @@ impl FromJson<Formula> for Formula {
                 "Atom" => Ok(Formula::Atom(try![map.read_item::<Proposition>("inner")])),
                 "Not" => Ok(Formula::Not(Box::new(try![map.read_item::<Formula>("inner")]))),
                 "EX" => Ok(Formula::EX(Box::new(try![map.read_item::<Formula>("inner")]))),
+                "AX" => Ok(Formula::AX(Box::new(try![map.read_item::<Formula>("inner")]))),
+                "EF" => Ok(Formula::EF(Box::new(try![map.read_item::<Formula>("inner")]))),
+                "AF" => Ok(Formula::AF(Box::new(try![map.read_item::<Formula>("inner")]))),
+                "EG" => Ok(Formula::EG(Box::new(try![map.read_item::<Formula>("inner")]))),
+                "AG" => Ok(Formula::AG(Box::new(try![map.read_item::<Formula>("inner")]))),
                 "And" => Ok(Formula::And(
                     Box::new(try![map.read_item::<Formula>("left")]),
                     Box::new(try![map.read_item::<Formula>("right")]),
                 )),
+                "Or" => Ok(Formula::Or(
+                    Box::new(try![map.read_item::<Formula>("left")]),
+                    Box::new(try![map.read_item::<Formula>("right")]),
+                )),
+                "Implies" => Ok(Formula::Implies(
+                    Box::new(try![map.read_item::<Formula>("left")]),
+                    Box::new(try![map.read_item::<Formula>("right")]),
+                )),
                 "EU" => Ok(Formula::EU(
                     Box::new(try![map.read_item::<Formula>("path")]),
                     Box::new(try![map.read_item::<Formula>("reach")]),
@@ -82,6 +294,28 @@ impl FromJson<Formula> for Formula {
                     Box::new(try![map.read_item::<Formula>("path")]),
                     Box::new(try![map.read_item::<Formula>("reach")]),
                 )),
+                "BoundedEF" => Ok(Formula::BoundedEF(
+                    Box::new(try![map.read_item::<Formula>("inner")]),
+                    try![map.read_item::<u64>("bound")] as usize
+                )),
+                "BoundedEU" => Ok(Formula::BoundedEU(
+                    Box::new(try![map.read_item::<Formula>("path")]),
+                    Box::new(try![map.read_item::<Formula>("reach")]),
+                    try![map.read_item::<u64>("bound")] as usize
+                )),
+                "Var" => Ok(Formula::Var(try![map.read_item::<String>("variable")])),
+                "Bind" => Ok(Formula::Bind(
+                    try![map.read_item::<String>("variable")],
+                    Box::new(try![map.read_item::<Formula>("inner")])
+                )),
+                "At" => Ok(Formula::At(
+                    try![map.read_item::<String>("variable")],
+                    Box::new(try![map.read_item::<Formula>("inner")])
+                )),
+                "Exists" => Ok(Formula::Exists(
+                    try![map.read_item::<String>("variable")],
+                    Box::new(try![map.read_item::<Formula>("inner")])
+                )),
                 _ => Err(DecoderError::UnknownVariantError("Unknown formula operator".to_string()))
             }
         })
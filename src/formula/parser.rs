@@ -0,0 +1,485 @@
+use super::Formula;
+use super::Formula::*;
+use super::Proposition;
+use super::CompareOp;
+use super::VertexSemantics;
+use super::super::model::VariableIndex;
+use super::super::model::ParameterIndex;
+use super::super::OdeModel;
+use super::super::parser::ParseError;
+
+/// Parse a CTL formula written in a compact text syntax, for example:
+///
+/// `EF (x > 3.5 && AG y < 2)` or `E [ x < 1 U y > 2 ]`
+///
+/// Supported operators are `!`, `&&`, `||`, `=>`, `EX`, `AX`, `EF`, `AF`, `EG`, `AG`,
+/// `E [ p U q ]` and `A [ p U q ]`. Propositions are `True`, `False`, comparisons of
+/// a variable with a real value (`x < 3.5`, `3.5 >= x`), ranges (`2 < x < 5`) and
+/// linear combinations of variables (`x + 2*y > 5`, `x > y`), which hold in rectangles
+/// where all vertices satisfy them. Constraints on parameters (`p1 < 2.5`, `p2 > p1`)
+/// hold in all states for the parameter values that satisfy them.
+/// `EF<=k p` and `E [ p U<=k q ]` limit the number of transitions to k.
+/// Hybrid operators bind state variables: `bind s: AX s` (or `↓s. AX s`), `exists s: ...`
+/// (or `∃s. ...`) and `at s: ...` (or `@s. ...`). State variable names must differ
+/// from model variables and parameters.
+/// Variable names are resolved against the given model.
+pub fn parse_formula(text: &str, model: &OdeModel) -> Result<Formula, ParseError> {
+    let tokens = try!(tokenize(text));
+    let mut cursor = Cursor { tokens: &tokens, position: 0, model: model, state_variables: vec![] };
+    let formula = try!(cursor.implication());
+    try!(cursor.expect(Token::End));
+    Ok(formula)
+}
+
+//Identifier in a proposition.
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Symbol {
+    Variable(VariableIndex), Parameter(ParameterIndex)
+}
+
+#[derive(Debug, PartialEq, Clone)]
+enum Token {
+    Number(f64), Identifier(String),
+    Less, Greater, LessEqual, GreaterEqual, And, Or, Not, Implies, Plus, Minus, Star,
+    LeftParen, RightParen, LeftBracket, RightBracket, Separator, End
+}
+
+struct Cursor<'a> {
+    tokens: &'a Vec<(Token, usize)>,
+    position: usize,
+    model: &'a OdeModel,
+    //state variables bound by enclosing hybrid operators
+    state_variables: Vec<String>
+}
+
+impl<'a> Cursor<'a> {
+
+    fn peek(&self) -> &Token {
+        &self.tokens[self.position].0
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.tokens[self.position].0.clone();
+        if token != Token::End {
+            self.position += 1;
+        }
+        token
+    }
+
+    fn error<T>(&self, message: String) -> Result<T, ParseError> {
+        Err(ParseError { line: 1, column: self.tokens[self.position].1, message: message })
+    }
+
+    fn expect(&mut self, token: Token) -> Result<(), ParseError> {
+        if *self.peek() == token {
+            self.next();
+            Ok(())
+        } else {
+            let message = format!("Expected {:?}, found {:?}", token, self.peek());
+            self.error(message)
+        }
+    }
+
+    //Optional step bound of a temporal operator: <= k
+    fn bound(&mut self) -> Result<Option<usize>, ParseError> {
+        if *self.peek() != Token::LessEqual {
+            return Ok(None);
+        }
+        self.next();
+        match self.peek().clone() {
+            Token::Number(value) if value >= 0.0 && value.fract() == 0.0 => {
+                self.next();
+                Ok(Some(value as usize))
+            }
+            _ => self.error("Expected number of steps".to_string())
+        }
+    }
+
+    //Binder of a hybrid operator: keyword, state variable and separator.
+    fn is_binder(&self) -> bool {
+        match (self.tokens.get(self.position + 1), self.tokens.get(self.position + 2)) {
+            (Some(&(Token::Identifier(_), _)), Some(&(Token::Separator, _))) => true,
+            _ => false
+        }
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        match self.peek() {
+            &Token::Identifier(ref name) => name == keyword,
+            _ => false
+        }
+    }
+
+    //implication is right associative
+    fn implication(&mut self) -> Result<Formula, ParseError> {
+        let left = try!(self.or());
+        if *self.peek() == Token::Implies {
+            self.next();
+            let right = try!(self.implication());
+            Ok(Implies(Box::new(left), Box::new(right)))
+        } else {
+            Ok(left)
+        }
+    }
+
+    fn or(&mut self) -> Result<Formula, ParseError> {
+        let mut result = try!(self.and());
+        while *self.peek() == Token::Or {
+            self.next();
+            let right = try!(self.and());
+            result = Or(Box::new(result), Box::new(right));
+        }
+        Ok(result)
+    }
+
+    fn and(&mut self) -> Result<Formula, ParseError> {
+        let mut result = try!(self.unary());
+        while *self.peek() == Token::And {
+            self.next();
+            let right = try!(self.unary());
+            result = And(Box::new(result), Box::new(right));
+        }
+        Ok(result)
+    }
+
+    fn unary(&mut self) -> Result<Formula, ParseError> {
+        let keyword = if let &Token::Identifier(ref name) = self.peek() { name.clone() } else { String::new() };
+        match self.peek().clone() {
+            Token::Not => {
+                self.next();
+                Ok(Not(Box::new(try!(self.unary()))))
+            }
+            Token::LeftParen => {
+                self.next();
+                let inner = try!(self.implication());
+                try!(self.expect(Token::RightParen));
+                Ok(inner)
+            }
+            Token::Identifier(_) if is_temporal(&keyword) => {
+                self.next();
+                let bound = try!(self.bound());
+                if bound.is_some() && keyword != "EF" {
+                    return self.error(format!("Step bound is not supported for {}", keyword));
+                }
+                let inner = Box::new(try!(self.unary()));
+                if let Some(bound) = bound {
+                    return Ok(BoundedEF(inner, bound));
+                }
+                Ok(match keyword.as_ref() {
+                    "EX" => EX(inner),
+                    "AX" => AX(inner),
+                    "EF" => EF(inner),
+                    "AF" => AF(inner),
+                    "EG" => EG(inner),
+                    _ => AG(inner)
+                })
+            }
+            Token::Identifier(_) if (keyword == "E" || keyword == "A") && self.tokens[self.position + 1].0 == Token::LeftBracket => {
+                self.next();
+                self.next();
+                let path = Box::new(try!(self.implication()));
+                if !self.is_keyword("U") {
+                    return self.error("Expected U".to_string());
+                }
+                self.next();
+                let bound = try!(self.bound());
+                if bound.is_some() && keyword != "E" {
+                    return self.error("Step bound is not supported for AU".to_string());
+                }
+                let reach = Box::new(try!(self.implication()));
+                try!(self.expect(Token::RightBracket));
+                Ok(match bound {
+                    Some(bound) => BoundedEU(path, reach, bound),
+                    None => if keyword == "E" { EU(path, reach) } else { AU(path, reach) }
+                })
+            }
+            Token::Identifier(_) if is_hybrid(&keyword) && self.is_binder() => {
+                self.next();
+                let name = match self.next() {
+                    Token::Identifier(name) => name,
+                    _ => unreachable!()
+                };
+                let position = self.position - 1;
+                if keyword == "at" && !self.state_variables.contains(&name) {
+                    return self.error_at(position, format!("Unbound state variable: {}", name));
+                }
+                let model = self.model;
+                if model.variables.iter().any(|v| v.name == name) || model.parameters.iter().any(|p| p.name == name) {
+                    return self.error_at(position, format!("State variable {} is also a model variable or parameter", name));
+                }
+                self.next();
+                self.state_variables.push(name.clone());
+                let inner = Box::new(try!(self.implication()));
+                self.state_variables.pop();
+                Ok(match keyword.as_ref() {
+                    "bind" => Bind(name, inner),
+                    "exists" => Exists(name, inner),
+                    _ => At(name, inner)
+                })
+            }
+            Token::Identifier(_) if self.state_variables.contains(&keyword) => {
+                self.next();
+                Ok(Var(keyword))
+            }
+            Token::Identifier(_) if keyword == "True" => {
+                self.next();
+                Ok(Atom(Proposition::True))
+            }
+            Token::Identifier(_) if keyword == "False" => {
+                self.next();
+                Ok(Atom(Proposition::False))
+            }
+            _ => self.proposition().map(Atom)
+        }
+    }
+
+    //x < 3.5, 3.5 >= x, 2 < x < 5, 5 > x > 2, x + 2*y > 5, x > y
+    fn proposition(&mut self) -> Result<Proposition, ParseError> {
+        let start = self.position;
+        let left = try!(self.linear());
+        let op = match compare_op(self.peek()) {
+            Some(op) => op,
+            None => return self.error("Expected comparison operator".to_string())
+        };
+        self.next();
+        let right = try!(self.linear());
+        if let Some(second) = compare_op(self.peek()) {
+            self.next();
+            let third = try!(self.linear());
+            let variable = match right.0.first() {
+                Some(&(Symbol::Variable(v), c)) if c == 1.0 && right.0.len() == 1 && right.1 == 0.0 => v,
+                _ => return self.error_at(start, "Expected range of a single variable".to_string())
+            };
+            if !left.0.is_empty() || !third.0.is_empty() || is_lower(&op) != is_lower(&second) {
+                return self.error_at(start, "Expected range of a single variable".to_string());
+            }
+            let (low, high) = if is_lower(&op) { (left.1, third.1) } else { (third.1, left.1) };
+            return if low < high {
+                Ok(Proposition::Range(variable, low, high))
+            } else {
+                let message = format!("Empty range of variable {}", self.model.variables[variable].name);
+                self.error_at(start, message)
+            };
+        }
+        //move everything to the left side: sum of c*x op constant
+        let mut coefficients = left.0;
+        for (variable, c) in right.0 {
+            add_coefficient(&mut coefficients, variable, -c);
+        }
+        coefficients.retain(|&(_, c)| c != 0.0);
+        let constant = right.1 - left.1;
+        let mut variables = vec![];
+        let mut parameters = vec![];
+        for (symbol, c) in coefficients {
+            match symbol {
+                Symbol::Variable(v) => variables.push((v, c)),
+                Symbol::Parameter(p) => parameters.push((p, c))
+            }
+        }
+        if !parameters.is_empty() {
+            return if variables.is_empty() {
+                Ok(Proposition::Parameters(parameters, op, constant))
+            } else {
+                self.error_at(start, "Proposition mixes variables and parameters".to_string())
+            };
+        }
+        match variables.len() {
+            0 => self.error_at(start, "Proposition does not contain a variable".to_string()),
+            1 => {
+                let (variable, c) = variables[0];
+                let op = if c < 0.0 { flip(&op) } else { op };
+                Ok(Proposition::Value(variable, op, constant / c))
+            }
+            _ => Ok(Proposition::Linear(variables, op, constant, VertexSemantics::AllVertices))
+        }
+    }
+
+    //Sum of terms c, x, c*x or x*c, returns symbol coefficients and the constant.
+    fn linear(&mut self) -> Result<(Vec<(Symbol, f64)>, f64), ParseError> {
+        let mut coefficients = vec![];
+        let mut constant = 0.0;
+        let mut sign = 1.0;
+        if *self.peek() == Token::Minus {
+            self.next();
+            sign = -1.0;
+        }
+        loop {
+            let (variable, value) = match self.next() {
+                Token::Number(value) => {
+                    if *self.peek() == Token::Star {
+                        self.next();
+                        (Some(try!(self.symbol())), value)
+                    } else {
+                        (None, value)
+                    }
+                }
+                Token::Identifier(name) => {
+                    self.position -= 1;
+                    let variable = try!(self.symbol());
+                    if *self.peek() == Token::Star {
+                        self.next();
+                        match self.next() {
+                            Token::Number(value) => (Some(variable), value),
+                            _ => {
+                                self.position -= 1;
+                                return self.error(format!("Expected number after {}*", name));
+                            }
+                        }
+                    } else {
+                        (Some(variable), 1.0)
+                    }
+                }
+                _ => {
+                    self.position -= 1;
+                    return self.error("Expected proposition".to_string());
+                }
+            };
+            match variable {
+                Some(variable) => add_coefficient(&mut coefficients, variable, sign * value),
+                None => constant += sign * value
+            }
+            sign = match *self.peek() {
+                Token::Plus => 1.0,
+                Token::Minus => -1.0,
+                _ => return Ok((coefficients, constant))
+            };
+            self.next();
+        }
+    }
+
+    fn symbol(&mut self) -> Result<Symbol, ParseError> {
+        if let Token::Identifier(name) = self.peek().clone() {
+            if let Some(v) = self.model.variables.iter().position(|v| v.name == name) {
+                self.next();
+                return Ok(Symbol::Variable(v));
+            }
+            if let Some(p) = self.model.parameters.iter().position(|p| p.name == name) {
+                self.next();
+                return Ok(Symbol::Parameter(p));
+            }
+            self.error(format!("Unknown variable or parameter: {}", name))
+        } else {
+            self.error("Expected variable or parameter".to_string())
+        }
+    }
+
+    fn error_at<T>(&mut self, position: usize, message: String) -> Result<T, ParseError> {
+        self.position = position;
+        self.error(message)
+    }
+}
+
+fn compare_op(token: &Token) -> Option<CompareOp> {
+    match token {
+        &Token::Less => Some(CompareOp::LT),
+        &Token::Greater => Some(CompareOp::GT),
+        &Token::LessEqual => Some(CompareOp::LE),
+        &Token::GreaterEqual => Some(CompareOp::GE),
+        _ => None
+    }
+}
+
+fn flip(op: &CompareOp) -> CompareOp {
+    match op {
+        &CompareOp::LT => CompareOp::GT,
+        &CompareOp::GT => CompareOp::LT,
+        &CompareOp::LE => CompareOp::GE,
+        &CompareOp::GE => CompareOp::LE
+    }
+}
+
+fn add_coefficient(coefficients: &mut Vec<(Symbol, f64)>, variable: Symbol, value: f64) {
+    if let Some(item) = coefficients.iter_mut().find(|item| item.0 == variable) {
+        item.1 += value;
+        return;
+    }
+    coefficients.push((variable, value));
+}
+
+fn is_lower(op: &CompareOp) -> bool {
+    match op {
+        &CompareOp::LT | &CompareOp::LE => true,
+        _ => false
+    }
+}
+
+fn is_hybrid(keyword: &str) -> bool {
+    match keyword {
+        "bind" | "at" | "exists" => true,
+        _ => false
+    }
+}
+
+fn is_temporal(keyword: &str) -> bool {
+    match keyword {
+        "EX" | "AX" | "EF" | "AF" | "EG" | "AG" => true,
+        _ => false
+    }
+}
+
+fn tokenize(text: &str) -> Result<Vec<(Token, usize)>, ParseError> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut result = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        let column = i + 1;
+        let next = chars.get(i + 1).cloned();
+        let (token, length) = match (chars[i], next) {
+            ('&', Some('&')) => (Token::And, 2),
+            ('|', Some('|')) => (Token::Or, 2),
+            ('=', Some('>')) => (Token::Implies, 2),
+            ('<', Some('=')) => (Token::LessEqual, 2),
+            ('>', Some('=')) => (Token::GreaterEqual, 2),
+            ('<', _) => (Token::Less, 1),
+            ('>', _) => (Token::Greater, 1),
+            ('!', _) => (Token::Not, 1),
+            ('+', _) => (Token::Plus, 1),
+            ('-', _) => (Token::Minus, 1),
+            ('*', _) => (Token::Star, 1),
+            ('(', _) => (Token::LeftParen, 1),
+            (')', _) => (Token::RightParen, 1),
+            ('[', _) => (Token::LeftBracket, 1),
+            (']', _) => (Token::RightBracket, 1),
+            (':', _) => (Token::Separator, 1),
+            ('.', next) if !next.map_or(false, |c| c.is_digit(10)) => (Token::Separator, 1),
+            ('↓', _) => (Token::Identifier("bind".to_string()), 1),
+            ('∃', _) => (Token::Identifier("exists".to_string()), 1),
+            ('@', _) => (Token::Identifier("at".to_string()), 1),
+            (c, _) if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            (c, _) if c.is_digit(10) || c == '.' => {
+                let start = i;
+                i += 1;
+                while i < chars.len() && (chars[i].is_digit(10) || chars[i] == '.' || chars[i] == 'e' || chars[i] == 'E'
+                    || ((chars[i] == '-' || chars[i] == '+') && (chars[i - 1] == 'e' || chars[i - 1] == 'E'))) {
+                    i += 1;
+                }
+                let number: String = chars[start..i].iter().cloned().collect();
+                match number.parse::<f64>() {
+                    Ok(n) => result.push((Token::Number(n), column)),
+                    Err(_) => return Err(ParseError {
+                        line: 1, column: column, message: format!("Invalid number: {}", number)
+                    })
+                }
+                continue;
+            }
+            (c, _) if c.is_alphabetic() || c == '_' => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+                result.push((Token::Identifier(chars[start..i].iter().cloned().collect()), column));
+                continue;
+            }
+            (c, _) => return Err(ParseError {
+                line: 1, column: column, message: format!("Unexpected character: {}", c)
+            })
+        };
+        result.push((token, column));
+        i += length;
+    }
+    result.push((Token::End, chars.len() + 1));
+    Ok(result)
+}
@@ -3,12 +3,14 @@ extern crate iterator_to_hash_map;
 use std::collections::HashMap;
 use std::collections::hash_map::Entry;
 use std::fmt::Debug;
+use std::f64::{INFINITY, NEG_INFINITY};
 use super::model::*;
 use super::parameters::Colors;
 use super::parameters::order_1::Order1;
 use super::formula::Proposition;
 use super::formula::Proposition::*;
 use super::formula::CompareOp;
+use super::formula::VertexSemantics;
 use super::checker::CheckerContext;
 
 use self::Progress::*;
@@ -16,20 +18,121 @@ use self::Progress::*;
 pub type StateSet = HashMap<StateID, Order1>;
 pub type StateSet2<C: Colors> = HashMap<StateID, C>;
 
-pub fn initial_states<C: Colors + Debug>(model: &Model, proposition: &Proposition) -> StateSet2<C> {
+///States and colors satisfying the proposition. Parameter constraints that can't be represented
+///by the colors are satisfied by no state, use `checker::validate` to detect them beforehand.
+pub fn initial_states<C: Colors + Debug + Clone>(model: &Model, proposition: &Proposition) -> StateSet2<C> {
    // println!["Initial states!"];
     match proposition {
         &False => HashMap::new(),
-        &True => enumerate_states(model, &0, &0, &(model.variables[0].len() - 2)),
-        &Equation(ref d, ref op, ref t) => {
-            match op {
-                &CompareOp::LT => enumerate_states(model, d, &0, &(t - 1)),
-                &CompareOp::GT => enumerate_states(model, d, t, &(model.variables[*d].len() - 2))
+        &True | &Equation(..) | &Value(..) | &Range(..) => match coordinate_range(model, proposition) {
+            Some((d, from, to)) => enumerate_states(model, &d, &from, &to),
+            None => HashMap::new()
+        },
+        &Linear(ref coefficients, ref op, ref value, ref semantics) => {
+            initial_states::<C>(model, &True).into_iter().filter(|&(ref state, _)| {
+                linear_holds(model, state, coefficients, op, *value, semantics)
+            }).collect()
+        }
+        &Parameters(ref coefficients, ref op, ref value) => {
+            match C::parameter_constraint(model, coefficients, op, *value) {
+                Some(ref colors) if colors.is_empty() => HashMap::new(),
+                Some(colors) => initial_states::<C>(model, &True).into_iter().map(|(state, _)| {
+                    (state, colors.clone())
+                }).collect(),
+                None => HashMap::new()
+            }
+        }
+    }
+}
+
+///Part of `initial_states` with the states for which `state % count == index`.
+///The states of the part are enumerated directly, the rest of the state space is never visited.
+pub fn initial_states_part<C: Colors + Debug + Clone>(
+    model: &Model, proposition: &Proposition, index: usize, count: usize
+) -> StateSet2<C> {
+    let state_count = model.variables.iter().fold(1, |acc, thresholds| acc * (thresholds.len() - 1));
+    let part = (0..).map(|i| index + i * count).take_while(|state| *state < state_count);
+    match proposition {
+        &False => HashMap::new(),
+        &True | &Equation(..) | &Value(..) | &Range(..) => match coordinate_range(model, proposition) {
+            Some((d, from, to)) => part.filter(|state| {
+                let coordinate = model.extract_coordinate(state, &d);
+                coordinate >= from && coordinate <= to
+            }).map(|state| (state, C::tt())).collect(),
+            None => HashMap::new()
+        },
+        &Linear(ref coefficients, ref op, ref value, ref semantics) => part.filter(|state| {
+            linear_holds(model, state, coefficients, op, *value, semantics)
+        }).map(|state| (state, C::tt())).collect(),
+        &Parameters(ref coefficients, ref op, ref value) => {
+            match C::parameter_constraint(model, coefficients, op, *value) {
+                Some(ref colors) if colors.is_empty() => HashMap::new(),
+                Some(colors) => part.map(|state| (state, colors.clone())).collect(),
+                None => HashMap::new()
             }
         }
     }
 }
 
+//A linear function attains its extremes over a rectangle in vertices,
+//so it is enough to check the minimal and maximal value.
+fn linear_holds(
+    model: &Model, state: &StateID, coefficients: &Vec<(VariableIndex, f64)>,
+    op: &CompareOp, value: f64, semantics: &VertexSemantics
+) -> bool {
+    let bounds = model.expand_state(state);
+    let (min, max) = coefficients.iter().fold((0.0, 0.0), |(min, max), &(v, c)| {
+        let (low, high) = bounds[v];
+        if c >= 0.0 { (min + c * low, max + c * high) } else { (min + c * high, max + c * low) }
+    });
+    match (op, semantics) {
+        (&CompareOp::LT, &VertexSemantics::AllVertices) => max < value,
+        (&CompareOp::LE, &VertexSemantics::AllVertices) => max <= value,
+        (&CompareOp::GT, &VertexSemantics::AllVertices) => min > value,
+        (&CompareOp::GE, &VertexSemantics::AllVertices) => min >= value,
+        (&CompareOp::LT, &VertexSemantics::SomeVertex) => min < value,
+        (&CompareOp::LE, &VertexSemantics::SomeVertex) => min <= value,
+        (&CompareOp::GT, &VertexSemantics::SomeVertex) => max > value,
+        (&CompareOp::GE, &VertexSemantics::SomeVertex) => max >= value
+    }
+}
+
+/// Inclusive range of coordinates of a single variable to which the proposition restricts
+/// the states (all other variables are unrestricted). Returns None for propositions that
+/// are not of this form and for propositions that are not satisfied by any state.
+pub fn coordinate_range(model: &Model, proposition: &Proposition)
+    -> Option<(VariableIndex, StateCoordinate, StateCoordinate)> {
+    match proposition {
+        &True => Some((0, 0, model.variables[0].len() - 2)),
+        &Equation(ref d, ref op, ref t) => match op {
+            &CompareOp::LT | &CompareOp::LE => t.checked_sub(1).map(|to| (*d, 0, to)),
+            &CompareOp::GT | &CompareOp::GE => {
+                let last = model.variables[*d].len() - 2;
+                if *t <= last { Some((*d, *t, last)) } else { None }
+            }
+        },
+        //Rectangle k spans thresholds k and k+1, it is included only if it is entirely on the valid side.
+        &Value(ref d, ref op, ref value) => match op {
+            &CompareOp::LT | &CompareOp::LE => value_range(model, d, NEG_INFINITY, *value),
+            &CompareOp::GT | &CompareOp::GE => value_range(model, d, *value, INFINITY)
+        },
+        &Range(ref d, ref low, ref high) => value_range(model, d, *low, *high),
+        _ => None
+    }
+}
+
+//Coordinates of states of which the variable d lies entirely within [low, high].
+fn value_range(model: &Model, d: &VariableIndex, low: f64, high: f64)
+    -> Option<(VariableIndex, StateCoordinate, StateCoordinate)> {
+    let thresholds = &model.variables[*d];
+    let from = thresholds.iter().position(|t| *t >= low);
+    let count = thresholds.iter().filter(|t| **t <= high).count();
+    match (from, count.checked_sub(2)) {
+        (Some(from), Some(to)) if from <= to => Some((*d, from, to)),
+        _ => None
+    }
+}
+
 fn enumerate_states<C: Colors + Debug>(model: &Model, d: &VariableIndex, from: &StateCoordinate, to: &StateCoordinate)
     -> StateSet2<C> {
     if to < from {
@@ -87,32 +190,9 @@ pub fn compute_directed_edges<C: Colors + Debug + Clone>(
     }*/
     match ctx.edge_cache.entry((*from, *time_flow)) {
         Entry::Vacant(o) => {
-            let mut self_loop = C::tt();
-            let mut result: StateSet2<C> = HashMap::new();
-            for i in 0..ctx.model.variables.len() {
-                let (high_in, high_out) = facet_colors::<C>(&ctx.model, &Facet(*from, Face(i, true)));
-                let (low_out, low_in) = facet_colors::<C>(&ctx.model, &Facet(*from, Face(i, false)));
-
-                //println!["high in:{:?} out:{:?}", high_in, high_out];
-                //println!["low in:{:?} out:{:?}", low_in, low_out];
-
-                let positive_flow: C = low_in.and(&high_out).and(&low_out.or(&high_in).not());
-                let negative_flow: C = low_out.and(&high_in).and(&low_in.or(&high_out).not());
-
-                if let Some(upper) = ctx.model.upper_neighbour(from, &i) {
-                    self_loop = self_loop.and(&positive_flow.not());
-                    let colors = if *time_flow { high_out } else { high_in };
-                    if !colors.is_empty() { result.insert(upper, colors); };
-                }
-                if let Some(lower) = ctx.model.lower_neighbour(from, &i) {
-                    self_loop = self_loop.and(&negative_flow.not());
-                    let colors = if *time_flow { low_out } else { low_in };
-                    if !colors.is_empty() { result.insert(lower, colors); };
-                }
-            }
-            if !self_loop.is_empty() {
-                result.insert(*from, self_loop);
-            }
+            //the context is created only for models supported by the colors
+            let result = directed_edges::<C>(&ctx.model, from, time_flow)
+                .expect("Colors do not support the model of the checker context");
             o.insert(result.clone());
             result
         }
@@ -125,10 +205,44 @@ pub fn compute_directed_edges<C: Colors + Debug + Clone>(
     }
 }
 
-pub fn facet_colors<C: Colors + Debug>(model: &Model, facet: &Facet) -> (C,C) {
-    fold_over_facet(model, facet, (C::ff(), C::ff()), |(down, up), vertex| {
-        let (negative, positive) = C::divide(model, &facet.variable_index(), vertex);
-        (down.or(&negative), up.or(&positive))
+///Compute successors (time_flow = true) or predecessors of a state without caching.
+///Fails if the colors can't represent the parameter constraints of the model.
+pub fn directed_edges<C: Colors + Debug>(model: &Model, from: &StateID, time_flow: &TimeFlow)
+    -> Result<StateSet2<C>, CompileError> {
+    let mut self_loop = C::tt();
+    let mut result: StateSet2<C> = HashMap::new();
+    for i in 0..model.variables.len() {
+        let (high_in, high_out) = try!(facet_colors::<C>(model, &Facet(*from, Face(i, true))));
+        let (low_out, low_in) = try!(facet_colors::<C>(model, &Facet(*from, Face(i, false))));
+
+        //println!["high in:{:?} out:{:?}", high_in, high_out];
+        //println!["low in:{:?} out:{:?}", low_in, low_out];
+
+        let positive_flow: C = low_in.and(&high_out).and(&low_out.or(&high_in).not());
+        let negative_flow: C = low_out.and(&high_in).and(&low_in.or(&high_out).not());
+
+        if let Some(upper) = model.upper_neighbour(from, &i) {
+            self_loop = self_loop.and(&positive_flow.not());
+            let colors = if *time_flow { high_out } else { high_in };
+            if !colors.is_empty() { result.insert(upper, colors); };
+        }
+        if let Some(lower) = model.lower_neighbour(from, &i) {
+            self_loop = self_loop.and(&negative_flow.not());
+            let colors = if *time_flow { low_out } else { low_in };
+            if !colors.is_empty() { result.insert(lower, colors); };
+        }
+    }
+    if !self_loop.is_empty() {
+        result.insert(*from, self_loop);
+    }
+    Ok(result)
+}
+
+pub fn facet_colors<C: Colors + Debug>(model: &Model, facet: &Facet) -> Result<(C,C), CompileError> {
+    fold_over_facet(model, facet, Ok((C::ff(), C::ff())), |colors, vertex| {
+        let (down, up) = try!(colors);
+        let (negative, positive) = try!(C::divide(model, &facet.variable_index(), vertex));
+        Ok((down.or(&negative), up.or(&positive)))
     })
 }
 
@@ -7,19 +7,33 @@ use json_utils::{FromJson, JsonMap, create_object, as_object};
 use rustc_serialize::json::{ToJson, Json, DecoderError};
 
 use self::model::Model;
+use self::model::CompileError;
 use self::model::Threshold;
 use self::types::Interval;
 use self::model::Summand2;
 use self::model::evaluable::Evaluable2;
 use self::model::evaluable::Function;
+use self::validation::ValidationReport;
+use self::validation::IssueKind;
+use self::formula::Formula;
+use self::formula::Proposition;
 
 pub mod parameters;
+pub mod approximation;
 pub mod parser;
 pub mod generator;
 pub mod formula;
 pub mod checker;
+pub mod symbolic;
+pub mod analysis;
+pub mod reachability;
 pub mod types;
 pub mod model;
+pub mod validation;
+pub mod names;
+pub mod bio;
+pub mod sbml;
+mod polynomial;
 
 use Evaluable::*;
 
@@ -34,20 +48,48 @@ pub struct OdeModel {
 
 impl OdeModel {
 
-    /// Check validity and if the model is invalid, return the cause.
+    /// Check validity and if the model is invalid, return the first cause.
     pub fn is_valid(&self) -> Option<String> {
-        self.parameters.iter().fold(None, |a, i| a.or_else(|| i.is_valid())).or_else(|| {
-            if self.variables.is_empty() {
-                Some("Model has no variables".to_string())
-            } else { None }
-        }).or_else(|| {
-            self.variables.iter().fold(None, |a, i| a.or_else(|| i.is_valid(self)))
-        })
+        self.validate().errors().first().map(|i| i.message.clone())
     }
 
-    pub fn compile(&self) -> Model {
-        if !self.is_multi_affine() {
-            panic!["Your model is not multi affine."];
+    /// Check validity of the whole model and report every issue that was found.
+    pub fn validate(&self) -> ValidationReport {
+        let mut report = ValidationReport::new();
+        for (i, parameter) in self.parameters.iter().enumerate() {
+            parameter.validate(&format!("parameters[{}]", i), &mut report);
+        }
+        if self.variables.is_empty() {
+            report.error(IssueKind::NoVariables, "variables".to_string(),
+                "Model has no variables".to_string());
+        }
+        for (i, variable) in self.variables.iter().enumerate() {
+            variable.validate(self, &format!("variables[{}]", i), &mut report);
+        }
+        report
+    }
+
+    /// Compile the model into its multi-affine form.
+    /// Hill and Sigmoid evaluables are replaced by their piecewise linear approximations.
+    ///
+    /// Fails if the model is not valid or some summand is not linear in parameters.
+    /// Use `Colors::supports` to check whether the compiled model can be handled
+    /// by a specific Colors implementation.
+    ///
+    /// Thresholds are not refined, so real-valued propositions that do not fall on a threshold
+    /// are under-approximated. Use `compile_for` to compile a model for given formulas;
+    /// `checker::validate` rejects formulas that the compiled model can't describe exactly.
+    pub fn compile(&self) -> Result<Model, CompileError> {
+        let report = self.validate();
+        if !report.is_valid() {
+            return Err(CompileError::InvalidModel(report));
+        }
+        for (v, var) in self.variables.iter().enumerate() {
+            for (s, summand) in var.equation.iter().enumerate() {
+                if summand.parameter_indices.len() > 1 {
+                    return Err(CompileError::ParameterProduct { variable: v, summand: s });
+                }
+            }
         }
         let parameter_bound: Vec<Interval> = self.parameters.iter().map(|&Parameter { ref range, .. }| {
             Interval(range.min, range.max)
@@ -56,14 +98,90 @@ impl OdeModel {
             var.thresholds.clone()
         }).collect();
         let equations: Vec<Vec<Summand2>> = self.variables.iter().map(|var| {
-            var.equation.iter().map(|i| i.compile()).collect()
+            var.equation.iter().map(|i| i.compile(self)).collect()
         }).collect();
-        Model::new(parameter_bound, variables, equations)
+        Ok(Model::new(parameter_bound, variables, equations))
+    }
+
+    ///Add the real values used in propositions of the formulas as thresholds, so that
+    ///`Value` and `Range` propositions describe exact sets of states.
+    ///Values outside of the current threshold span are ignored. Threshold indices of `Equation`
+    ///propositions are not updated, use `Formula::with_values` before refining.
+    pub fn refine_thresholds(&self, formulas: &[&Formula]) -> OdeModel {
+        let mut result = self.clone();
+        for prop in formulas.iter().flat_map(|f| f.propositions()) {
+            let (variable, values) = match prop {
+                &Proposition::Value(variable, _, value) => (variable, vec![value]),
+                &Proposition::Range(variable, low, high) => (variable, vec![low, high]),
+                _ => continue
+            };
+            if let Some(var) = result.variables.get_mut(variable) {
+                for value in values {
+                    let first = var.thresholds.first().cloned().unwrap_or(value);
+                    let last = var.thresholds.last().cloned().unwrap_or(value);
+                    if value > first && value < last && !var.thresholds.contains(&value) {
+                        var.thresholds.push(value);
+                    }
+                }
+                var.thresholds.sort_by(|a, b| a.partial_cmp(b).expect("Floating point error"));
+            }
+        }
+        result
+    }
+
+    ///Compile the model with thresholds refined for given formulas.
+    pub fn compile_for(&self, formulas: &[&Formula]) -> Result<Model, CompileError> {
+        self.refine_thresholds(formulas).compile()
     }
 
     pub fn is_multi_affine(&self) -> bool {
         self.variables.iter().all(|i| i.is_multi_affine())
     }
+
+    /// Create a multi-affine abstraction of this model. Every Hill and Sigmoid evaluable
+    /// is replaced by a ramp approximation and the breakpoints of the approximation
+    /// are added to the thresholds of the corresponding variable.
+    pub fn approximate(&self) -> OdeModel {
+        let mut breakpoints: Vec<Vec<f64>> = vec![vec![]; self.variables.len()];
+        let variables: Vec<Variable> = self.variables.iter().map(|var| {
+            Variable {
+                equation: var.equation.iter().map(|summand| {
+                    Summand {
+                        evaluables: summand.evaluables.iter().map(|e| {
+                            let linear = e.linearize(self);
+                            if !e.is_multi_affine() {
+                                if let RampApproximation { variable_index, ref approximation } = linear {
+                                    for p in approximation {
+                                        breakpoints[variable_index].push(p.threshold);
+                                    }
+                                }
+                            }
+                            linear
+                        }).collect(),
+                        .. summand.clone()
+                    }
+                }).collect(),
+                .. var.clone()
+            }
+        }).collect();
+        OdeModel {
+            variables: variables.into_iter().zip(breakpoints.into_iter()).map(|(var, points)| {
+                let mut thresholds = var.thresholds.clone();
+                thresholds.extend(points.into_iter().filter(|t| {
+                    *t >= var.range.min && *t <= var.range.max
+                }));
+                thresholds.sort_by(|a, b| a.partial_cmp(b).expect("Floating point error"));
+                thresholds.dedup();
+                Variable { thresholds: thresholds, .. var }
+            }).collect(),
+            .. self.clone()
+        }
+    }
+
+    /// Same as `to_json`, but equations refer to variables and parameters by name.
+    pub fn to_json_with_names(&self) -> Json {
+        names::write_names(&self.to_json())
+    }
 }
 
 impl ToJson for OdeModel {
@@ -76,9 +194,14 @@ impl ToJson for OdeModel {
     }
 }
 
+/// Equations can refer to variables and parameters either by index or by name,
+/// see the `names` module. Instead of "equation", a variable can also specify
+/// an "equationText" in the syntax of `parser::EquationParser`.
 impl FromJson<OdeModel> for OdeModel {
     fn from_json(json: &Json) -> Result<OdeModel, DecoderError> {
-        as_object(json, |map| {
+        let json = try!(parser::expand_equation_text(json));
+        let json = try!(names::resolve_names(&json));
+        as_object(&json, |map| {
             Ok(OdeModel {
                 name: try!(map.read_item::<String>("name")),
                 parameters: try!(map.read_item::<Vec<Parameter>>("parameters")),
@@ -100,18 +223,58 @@ pub struct Variable {
 }
 
 impl Variable {
-    fn is_valid(&self, model: &OdeModel) -> Option<String> {
-        self.range.is_valid().or_else(|| {
-            if self.thresholds.is_empty() {
-                Some(format!("Variable {} has no thresholds", self.name))
-            } else { None }
-        }).or_else(|| {
-            if self.equation.is_empty() {
-                Some(format!("Variable {} has an empty equation", self.name))
-            } else { None }
-        }).or_else(|| {
-            self.equation.iter().fold(None, |a, i| a.or_else(|| i.is_valid(model)))
-        })
+    fn validate(&self, model: &OdeModel, path: &str, report: &mut ValidationReport) {
+        self.range.validate(&format!("{}.range", path), report);
+        if self.thresholds.is_empty() {
+            report.error(IssueKind::NoThresholds, format!("{}.thresholds", path),
+                format!("Variable {} has no thresholds", self.name));
+        } else if self.thresholds.len() == 1 {
+            //such a variable has no rectangles, but the model is still well formed
+            report.warning(IssueKind::SingleThreshold, format!("{}.thresholds", path),
+                format!("Variable {} has only one threshold", self.name));
+        }
+        self.validate_thresholds(path, report);
+        if self.equation.is_empty() {
+            report.error(IssueKind::EmptyEquation, format!("{}.equation", path),
+                format!("Variable {} has an empty equation", self.name));
+        }
+        for (i, summand) in self.equation.iter().enumerate() {
+            summand.validate(model, &format!("{}.equation[{}]", path, i), report);
+        }
+    }
+
+    fn validate_thresholds(&self, path: &str, report: &mut ValidationReport) {
+        for (i, t) in self.thresholds.iter().enumerate() {
+            let t_path = format!("{}.thresholds[{}]", path, i);
+            if !check_finite(*t, &t_path, report) {
+                continue;
+            }
+            if *t < self.range.min || *t > self.range.max {
+                report.error(IssueKind::ThresholdOutOfRange, t_path.clone(),
+                    format!("Threshold {} of variable {} is outside of range [{}, {}]",
+                        t, self.name, self.range.min, self.range.max));
+            }
+            if i > 0 {
+                let previous = self.thresholds[i - 1];
+                if previous == *t {
+                    report.error(IssueKind::DuplicateThreshold, t_path,
+                        format!("Duplicate threshold {} of variable {}", t, self.name));
+                } else if previous > *t {
+                    report.error(IssueKind::UnsortedThresholds, t_path,
+                        format!("Thresholds of variable {} are not sorted: {} > {}", self.name, previous, t));
+                }
+            }
+        }
+        if let (Some(first), Some(last)) = (self.thresholds.first(), self.thresholds.last()) {
+            if *first != self.range.min {
+                report.warning(IssueKind::MissingBoundaryThreshold, format!("{}.thresholds", path),
+                    format!("Variable {} has no threshold at range minimum {}", self.name, self.range.min));
+            }
+            if *last != self.range.max {
+                report.warning(IssueKind::MissingBoundaryThreshold, format!("{}.thresholds", path),
+                    format!("Variable {} has no threshold at range maximum {}", self.name, self.range.max));
+            }
+        }
     }
 
     fn is_multi_affine(&self) -> bool {
@@ -182,8 +345,8 @@ pub struct Parameter {
 }
 
 impl Parameter {
-    fn is_valid(&self) -> Option<String> {
-        self.range.is_valid()
+    fn validate(&self, path: &str, report: &mut ValidationReport) {
+        self.range.validate(&format!("{}.range", path), report);
     }
 }
 
@@ -216,10 +379,13 @@ pub struct Range {
 }
 
 impl Range {
-    fn is_valid(&self) -> Option<String> {
-        if self.min >= self.max {
-            Some(format!("Empty range: {} >= {}", self.min, self.max))
-        } else { None }
+    fn validate(&self, path: &str, report: &mut ValidationReport) {
+        let finite = check_finite(self.min, &format!("{}.min", path), report);
+        let finite = check_finite(self.max, &format!("{}.max", path), report) && finite;
+        if finite && self.min >= self.max {
+            report.error(IssueKind::EmptyRange, path.to_string(),
+                format!("Empty range: {} >= {}", self.min, self.max));
+        }
     }
 }
 
@@ -254,26 +420,25 @@ pub struct Summand {
 }
 
 impl Summand {
-    fn compile(&self) -> Summand2 {
+    fn compile(&self, model: &OdeModel) -> Summand2 {
         Summand2 {
             multiplier: self.constant,
             variable_indices: self.variable_indices.clone(),
             parameter_indices: self.parameter_indices.clone(),
-            functions: self.evaluables.iter().map(|e| { e.compile() }).collect()
+            functions: self.evaluables.iter().map(|e| { e.compile(model) }).collect()
         }
     }
-    fn is_valid(&self, model: &OdeModel) -> Option<String> {
-        self.variable_indices.iter().fold(None, |a, i| {
-            a.or_else(|| check_variable_index(*i, model))
-        }).or_else(|| {
-            self.parameter_indices.iter().fold(None, |a, i| {
-                a.or_else(|| check_parameter_index(*i, model))
-            })
-        }).or_else(|| {
-            self.evaluables.iter().fold(None, |a, i| {
-                a.or_else(|| i.is_valid(model))
-            })
-        })
+    fn validate(&self, model: &OdeModel, path: &str, report: &mut ValidationReport) {
+        check_finite(self.constant, &format!("{}.constant", path), report);
+        for (i, index) in self.variable_indices.iter().enumerate() {
+            check_variable_index(*index, model, &format!("{}.variableIndices[{}]", path, i), report);
+        }
+        for (i, index) in self.parameter_indices.iter().enumerate() {
+            check_parameter_index(*index, model, &format!("{}.parameterIndices[{}]", path, i), report);
+        }
+        for (i, evaluable) in self.evaluables.iter().enumerate() {
+            evaluable.validate(model, &format!("{}.evaluables[{}]", path, i), report);
+        }
     }
 
     fn is_multi_affine(&self) -> bool {
@@ -320,8 +485,33 @@ pub enum Evaluable {
 }
 
 impl Evaluable {
-    fn compile(&self) -> Evaluable2 {
+
+    /// Replace a non multi-affine evaluable with its ramp approximation.
+    /// Multi-affine evaluables are returned unchanged.
+    pub fn linearize(&self, model: &OdeModel) -> Evaluable {
         match self {
+            &Hill { variable_index, theta, n, a, b } => {
+                RampApproximation {
+                    variable_index: variable_index,
+                    approximation: approximation::sample(&model.variables[variable_index], |x| {
+                        approximation::hill(x, theta, n, a, b)
+                    })
+                }
+            }
+            &Sigmoid { variable_index, theta, k, a, b } => {
+                RampApproximation {
+                    variable_index: variable_index,
+                    approximation: approximation::sample(&model.variables[variable_index], |x| {
+                        approximation::sigmoid(x, theta, k, a, b)
+                    })
+                }
+            }
+            other => other.clone()
+        }
+    }
+
+    fn compile(&self, model: &OdeModel) -> Evaluable2 {
+        match &self.linearize(model) {
             &Step { variable_index, theta, a, b } => {
                 Evaluable2(variable_index, Function::Step {
                     a: a, b: b, theta: theta
@@ -342,21 +532,67 @@ impl Evaluable {
                     }).collect()
                 })
             }
-            _ => panic!["Function is not multi affine"]
+            _ => unreachable!()
         }
     }
-    fn is_valid(&self, model: &OdeModel) -> Option<String> {
+    fn validate(&self, model: &OdeModel, path: &str, report: &mut ValidationReport) {
+        let field = |name: &str| format!("{}.{}", path, name);
         match self {
-            &Hill { variable_index, .. } => check_variable_index(variable_index, model),
-            &Sigmoid { variable_index, .. } => check_variable_index(variable_index, model),
-            &Step { variable_index, .. } => check_variable_index(variable_index, model),
-            &Ramp { variable_index, .. } => check_variable_index(variable_index, model),
+            &Hill { variable_index, theta, n, a, b } => {
+                check_variable_index(variable_index, model, path, report);
+                //theta^n is the denominator in 0, so a zero threshold makes the function undefined there
+                if check_finite(theta, &field("theta"), report) && theta <= 0.0 {
+                    report.error(IssueKind::NonPositiveHillThreshold, field("theta"),
+                        format!("Hill threshold is not positive: {}", theta));
+                }
+                check_finite(a, &field("a"), report);
+                check_finite(b, &field("b"), report);
+                if check_finite(n, &field("n"), report) && n < 0.0 {
+                    report.error(IssueKind::NegativeHillCoefficient, field("n"),
+                        format!("Negative Hill coefficient: {}", n));
+                }
+            }
+            &Sigmoid { variable_index, theta, k, a, b } => {
+                check_variable_index(variable_index, model, path, report);
+                check_finite(theta, &field("theta"), report);
+                check_finite(k, &field("k"), report);
+                check_finite(a, &field("a"), report);
+                check_finite(b, &field("b"), report);
+            }
+            &Step { variable_index, theta, a, b } => {
+                check_variable_index(variable_index, model, path, report);
+                check_finite(theta, &field("theta"), report);
+                check_finite(a, &field("a"), report);
+                check_finite(b, &field("b"), report);
+            }
+            &Ramp { variable_index, low, high, a, b } => {
+                check_variable_index(variable_index, model, path, report);
+                check_finite(a, &field("a"), report);
+                check_finite(b, &field("b"), report);
+                let finite = check_finite(low, &field("lowThreshold"), report);
+                if check_finite(high, &field("highThreshold"), report) && finite && low >= high {
+                    report.error(IssueKind::EmptyRamp, path.to_string(),
+                        format!("Empty ramp: {} >= {}", low, high));
+                }
+            }
             &RampApproximation { variable_index, ref approximation } => {
-                check_variable_index(variable_index, model).or_else(|| {
-                    if approximation.is_empty() {
-                        Some("RampApproximation with no points".to_string())
-                    } else { None }
-                })
+                check_variable_index(variable_index, model, path, report);
+                if approximation.is_empty() {
+                    report.error(IssueKind::EmptyRampApproximation, field("approximation"),
+                        "RampApproximation with no points".to_string());
+                }
+                for (i, point) in approximation.iter().enumerate() {
+                    let p_path = format!("{}.approximation[{}]", path, i);
+                    check_finite(point.value, &format!("{}.value", p_path), report);
+                    if check_finite(point.threshold, &format!("{}.threshold", p_path), report) && i > 0 {
+                        let previous = approximation[i - 1].threshold;
+                        if previous >= point.threshold {
+                            report.error(IssueKind::UnsortedApproximation, p_path,
+                                format!("Approximation points are not strictly increasing: {} >= {}",
+                                    previous, point.threshold));
+                        }
+                    }
+                }
             }
         }
     }
@@ -501,14 +737,25 @@ impl FromJson<Point> for Point {
 
 // Utility stuff
 
-fn check_variable_index(i: usize, model: &OdeModel) -> Option<String> {
+fn check_variable_index(i: usize, model: &OdeModel, path: &str, report: &mut ValidationReport) {
     if i >= model.variables.len() {
-        Some(format!("Invalid variable index: {}", i))
-    } else { None }
+        report.error(IssueKind::InvalidVariableIndex, path.to_string(),
+            format!("Invalid variable index: {}", i));
+    }
+}
+
+//Report an error if the value is NaN or infinite. Returns true when the value is finite.
+fn check_finite(value: f64, path: &str, report: &mut ValidationReport) -> bool {
+    if !value.is_finite() {
+        report.error(IssueKind::NotFinite, path.to_string(),
+            format!("Value is not a finite number: {}", value));
+        false
+    } else { true }
 }
 
-fn check_parameter_index(i: usize, model: &OdeModel) -> Option<String> {
+fn check_parameter_index(i: usize, model: &OdeModel, path: &str, report: &mut ValidationReport) {
     if i >= model.parameters.len() {
-        Some(format!("Invalid parameter index: {}", i))
-    } else { None }
+        report.error(IssueKind::InvalidParameterIndex, path.to_string(),
+            format!("Invalid parameter index: {}", i));
+    }
 }
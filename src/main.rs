@@ -13,13 +13,21 @@ use json_utils::{FromJson, as_object, JsonMap, create_object};
 //use json_utils::{FromJson, JsonMap, , as_object};
 
 use std::env;
+use std::io::Write;
+use std::process;
+use std::path::Path;
 use ode_model::*;
 use ode_model::model::*;
 use ode_model::generator::StateSet2;
 use std::fs::File;
 use ode_model::formula::Formula;
+use ode_model::formula::parser::parse_formula;
 use ode_model::checker::check;
 use ode_model::checker::CheckerContext;
+use ode_model::checker::validate;
+use ode_model::checker::Fairness;
+use ode_model::checker::parallel::{ParallelContext, check_parallel};
+use ode_model::checker::distributed::{check_distributed, run_worker};
 
 use ode_model::parameters::Colors;
 use ode_model::parameters::order_1::*;
@@ -37,22 +45,48 @@ fn main() {
         Z3 = &z3 as *const Context;
     }
     let args: Vec<_> = env::args().collect();
-    let mut config_file = File::open(args[1].clone()).unwrap();
-    //let mut model_file = File::open(args[0].clone()).unwrap();
-    //let mut property_file = File::open(args[2].clone()).unwrap();
-    //let prop = Formula::from_json(&Json::from_reader(&mut property_file).unwrap()).unwrap();
-    let config = Config2::from_json(&Json::from_reader(&mut config_file).unwrap()).unwrap();
-    let full_model = config.model;
-    let model = full_model.compile();
-    let mut ctx = CheckerContext::new(model.clone());
-    let result = config.formulas.iter().map(|&AbstractPair { ref first, ref second }| {
+    //worker process of the distributed mode: --worker <socket> <index> <count> <config>
+    if args.len() > 5 && args[1] == "--worker" {
+        let (config, _, model) = prepare(&args[5]);
+        let index = args[3].parse::<usize>().unwrap();
+        let count = args[4].parse::<usize>().unwrap();
+        let formulas: Vec<&Formula> = config.formulas.iter().map(|pair| &pair.second).collect();
+        let socket = Path::new(&args[2]);
+        if let Err(error) = run_worker::<Order1>(socket, index, count, model, Fairness::Disabled, &formulas) {
+            exit_with_error(&error.to_string());
+        }
+        return;
+    }
+    let (config, full_model, model) = prepare(&args[1]);
+    let checked: Vec<StateSet2<Order1>> = if config.processes > 1 {
+        match check_distributed::<Order1>(config.processes, config.formulas.len(), &[args[1].clone()]) {
+            Ok(results) => results,
+            Err(error) => exit_with_error(&error.to_string())
+        }
+    } else if config.threads > 1 {
+        let ctx = match ParallelContext::new(model.clone(), config.threads) {
+            Ok(ctx) => ctx,
+            Err(error) => exit_with_error(&error.to_string())
+        };
+        config.formulas.iter().map(|pair| match check_parallel::<Order1>(&ctx, &pair.second) {
+            Ok(result) => result,
+            Err(error) => exit_with_error(&format!("{}: {}", pair.first, error))
+        }).collect()
+    } else {
+        let mut ctx = match CheckerContext::new(model.clone()) {
+            Ok(ctx) => ctx,
+            Err(error) => exit_with_error(&error.to_string())
+        };
+        config.formulas.iter().map(|pair| check::<Order1>(&mut ctx, &pair.second)).collect()
+    };
+    let bounds = Order1::model_bounds(&model);
+    let result = config.formulas.iter().zip(checked.into_iter()).map(|(pair, checked)| {
         //println!("Verify: {:?}", second);
-        let checked = check::<Order1>(&mut ctx, second);
         let mut bounded = HashMap::new();
         for (state, colors) in checked {
-            bounded.insert(state.clone(), colors.and(&Order1::model_bounds(&ctx.model)));
+            bounded.insert(state.clone(), colors.and(&bounds));
         }
-        (first.clone(), bounded)
+        (pair.first.clone(), bounded)
     }).collect::<Vec<(String, StateSet2<Order1>)>>();
     //println!["Verify: {:?}", formula];
     //println!["Model: {:?}", full_model];
@@ -63,9 +97,45 @@ fn main() {
     }*/
 }
 
+//Read the config, refine thresholds for its formulas and compile the model.
+fn prepare(path: &str) -> (Config2, OdeModel, Model) {
+    let mut config_file = File::open(path).unwrap();
+    //let mut model_file = File::open(args[0].clone()).unwrap();
+    //let mut property_file = File::open(args[2].clone()).unwrap();
+    //let prop = Formula::from_json(&Json::from_reader(&mut property_file).unwrap()).unwrap();
+    let mut config = Config2::from_json(&Json::from_reader(&mut config_file).unwrap()).unwrap();
+    config.formulas = config.formulas.iter().map(|&AbstractPair { ref first, ref second }| {
+        AbstractPair { first: first.clone(), second: second.with_values(&config.model) }
+    }).collect();
+    let full_model = config.model.refine_thresholds(&config.formulas.iter().map(|pair| &pair.second).collect::<Vec<_>>());
+    let model = match full_model.compile() {
+        Ok(model) => model,
+        Err(error) => exit_with_error(&error.to_string())
+    };
+    if let Err(error) = Order1::supports(&model) {
+        exit_with_error(&error.to_string());
+    }
+    for pair in &config.formulas {
+        if pair.second.is_hybrid() && (config.threads > 1 || config.processes > 1) {
+            exit_with_error(&format!("{}: hybrid operators are only supported with one thread and process", pair.first));
+        }
+        if let Err(error) = validate::<Order1>(&model, &pair.second) {
+            exit_with_error(&format!("{}: {}", pair.first, error));
+        }
+    }
+    (config, full_model, model)
+}
+
+fn exit_with_error(message: &str) -> ! {
+    writeln!(&mut std::io::stderr(), "{}", message).unwrap();
+    process::exit(1)
+}
+
 struct Config2 {
     model: OdeModel,
-    formulas: Vec<AbstractPair>
+    formulas: Vec<AbstractPair>,
+    threads: usize,
+    processes: usize
 }
 
 struct AbstractPair {
@@ -76,20 +146,37 @@ struct AbstractPair {
 impl FromJson<Config2> for Config2 {
     fn from_json(data: &Json) -> Result<Config2, DecoderError> {
         as_object(data, |map| {
+            let model = try![map.read_item::<OdeModel>("model")];
+            let mut formulas = vec![];
+            match data.find("formulas").and_then(|f| f.as_array()) {
+                Some(items) => for item in items {
+                    formulas.push(try!(AbstractPair::read(item, &model)));
+                },
+                None => return Err(DecoderError::MissingFieldError("formulas".to_string()))
+            }
             Ok(Config2 {
-                model: try![map.read_item::<OdeModel>("model")],
-                formulas: try![map.read_item::<Vec<AbstractPair>>("formulas")]
+                model: model,
+                formulas: formulas,
+                threads: try![map.read_optional_item::<u64>("threads")].unwrap_or(1) as usize,
+                processes: try![map.read_optional_item::<u64>("processes")].unwrap_or(1) as usize
             })
         })
     }
 }
 
-impl FromJson<AbstractPair> for AbstractPair {
-    fn from_json(data: &Json) -> Result<AbstractPair, DecoderError> {
+impl AbstractPair {
+    //The formula can be either a JSON object or a string in the syntax of formula::parser.
+    fn read(data: &Json, model: &OdeModel) -> Result<AbstractPair, DecoderError> {
         as_object(data, |map| {
+            let second = match data.find("second").and_then(|s| s.as_string()) {
+                Some(text) => try!(parse_formula(text, model).map_err(|e| {
+                    DecoderError::ApplicationError(format!("Invalid formula {}: {}", text, e))
+                })),
+                None => try![map.read_item::<Formula>("second")]
+            };
             Ok(AbstractPair {
                 first: try![map.read_item::<String>("first")],
-                second: try![map.read_item::<Formula>("second")]
+                second: second
             })
         })
     }
@@ -1,6 +1,10 @@
 pub mod evaluable;
 
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Error;
 use types::Interval;
+use validation::ValidationReport;
 use super::parameters::order_1::Order1;
 use super::parameters::order_1::Clause;
 use self::evaluable::Evaluable2;
@@ -42,6 +46,43 @@ impl Facet {
     }
 }
 
+/// Reasons why a model can't be compiled or handled by a Colors implementation.
+#[derive(Debug, PartialEq, Clone)]
+pub enum CompileError {
+    /// Model did not pass validation, report contains all issues.
+    InvalidModel(ValidationReport),
+    /// Summand contains a product of parameters, so the equation is not linear in parameters.
+    ParameterProduct { variable: VariableIndex, summand: usize },
+    /// Equation depends on more than one parameter.
+    MultipleParameters { variable: VariableIndex },
+    /// Equation depends on a parameter, but the colors can't represent parameters.
+    Parametrised { variable: VariableIndex },
+    /// Derivation of the variable in some vertex is too large to be represented exactly.
+    NotRepresentable { variable: VariableIndex }
+}
+
+impl Display for CompileError {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        match self {
+            &CompileError::InvalidModel(ref report) => {
+                try!(f.write_str("Invalid model:"));
+                for issue in report.errors() {
+                    try!(write!(f, " {}: {};", issue.path, issue.message));
+                }
+                Ok(())
+            }
+            &CompileError::ParameterProduct { variable, summand } =>
+                write!(f, "Summand {} of variable {} contains more than one parameter", summand, variable),
+            &CompileError::MultipleParameters { variable } =>
+                write!(f, "Equation of variable {} contains more than one parameter", variable),
+            &CompileError::Parametrised { variable } =>
+                write!(f, "Equation of variable {} contains parameters", variable),
+            &CompileError::NotRepresentable { variable } =>
+                write!(f, "Equation of variable {} has coefficients that can't be represented", variable),
+        }
+    }
+}
+
 // A "compiled" model stripped of all unnecessary information
 #[derive(Clone)]
 pub struct Model {
@@ -134,6 +175,19 @@ impl Model {
         })
     }
 
+    /// Return indices of all distinct parameters used in the equation of given variable.
+    pub fn equation_parameters(&self, variable: &VariableIndex) -> Vec<ParameterIndex> {
+        let mut result: Vec<ParameterIndex> = vec![];
+        for summand in &self.equations[*variable] {
+            for p in &summand.parameter_indices {
+                if !result.contains(p) {
+                    result.push(*p);
+                }
+            }
+        }
+        result
+    }
+
     pub fn full_order_1_colors(&self) -> Order1 {
         Order1(vec![Clause(self.parameter_bounds.clone())])
     }
@@ -0,0 +1,140 @@
+use std::collections::BTreeMap;
+
+use rustc_serialize::json::{Json, DecoderError};
+
+// Equations in the JSON format can refer to variables and parameters either by index
+// or by name. Summands use "variableIndices"/"parameterIndices" or "variables"/"parameters",
+// evaluables use "variableIndex" or "variable". The functions in this module translate
+// between the two forms before decoding and after encoding a model.
+
+type Object = BTreeMap<String, Json>;
+
+/// Replace all name references in the equations of a JSON model with indices.
+pub fn resolve_names(json: &Json) -> Result<Json, DecoderError> {
+    let variables = declared_names(json, "variables");
+    let parameters = declared_names(json, "parameters");
+    let mut result = json.clone();
+    try!(for_each_reference(&mut result, &mut |summand| {
+        try!(names_to_indices(summand, "variables", "variableIndices", &variables, "variable"));
+        names_to_indices(summand, "parameters", "parameterIndices", &parameters, "parameter")
+    }, &mut |evaluable| {
+        if let Some(name) = evaluable.remove("variable") {
+            if evaluable.contains_key("variableIndex") {
+                return Err(DecoderError::ApplicationError(
+                    "Evaluable contains both variable and variableIndex".to_string()
+                ));
+            }
+            let index = try!(resolve(&name, &variables, "variable"));
+            evaluable.insert("variableIndex".to_string(), index);
+        }
+        Ok(())
+    }));
+    Ok(result)
+}
+
+/// Replace all index references in the equations of a JSON model with names.
+/// References with indices that are out of bounds are left unchanged.
+pub fn write_names(json: &Json) -> Json {
+    let variables = declared_names(json, "variables");
+    let parameters = declared_names(json, "parameters");
+    let mut result = json.clone();
+    for_each_reference(&mut result, &mut |summand| {
+        indices_to_names(summand, "variableIndices", "variables", &variables);
+        indices_to_names(summand, "parameterIndices", "parameters", &parameters);
+        Ok(())
+    }, &mut |evaluable| {
+        let name = evaluable.get("variableIndex").and_then(|i| i.as_u64()).and_then(|i| {
+            variables.get(i as usize).cloned()
+        });
+        if let Some(name) = name {
+            evaluable.remove("variableIndex");
+            evaluable.insert("variable".to_string(), Json::String(name));
+        }
+        Ok(())
+    }).unwrap();
+    result
+}
+
+/// Names of all objects in the top level array under given key.
+pub fn declared_names(json: &Json, key: &str) -> Vec<String> {
+    json.find(key).and_then(|items| items.as_array()).map(|items| {
+        items.iter().map(|item| {
+            item.find("name").and_then(|name| name.as_string()).unwrap_or("").to_string()
+        }).collect()
+    }).unwrap_or(vec![])
+}
+
+//Call given functions on every summand and evaluable object in the model equations.
+fn for_each_reference(
+    json: &mut Json,
+    summand_fn: &mut FnMut(&mut Object) -> Result<(), DecoderError>,
+    evaluable_fn: &mut FnMut(&mut Object) -> Result<(), DecoderError>
+) -> Result<(), DecoderError> {
+    if let &mut Json::Object(ref mut model) = json {
+        if let Some(&mut Json::Array(ref mut variables)) = model.get_mut("variables") {
+            for variable in variables.iter_mut() {
+                if let &mut Json::Object(ref mut variable) = variable {
+                    if let Some(&mut Json::Array(ref mut equation)) = variable.get_mut("equation") {
+                        for summand in equation.iter_mut() {
+                            if let &mut Json::Object(ref mut summand) = summand {
+                                try!(summand_fn(summand));
+                                if let Some(&mut Json::Array(ref mut evaluables)) = summand.get_mut("evaluables") {
+                                    for evaluable in evaluables.iter_mut() {
+                                        if let &mut Json::Object(ref mut evaluable) = evaluable {
+                                            try!(evaluable_fn(evaluable));
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn names_to_indices(
+    object: &mut Object, names_key: &str, indices_key: &str, declared: &Vec<String>, kind: &str
+) -> Result<(), DecoderError> {
+    if let Some(names) = object.remove(names_key) {
+        if object.contains_key(indices_key) {
+            return Err(DecoderError::ApplicationError(
+                format!("Summand contains both {} and {}", names_key, indices_key)
+            ));
+        }
+        if let Json::Array(names) = names {
+            let mut indices = vec![];
+            for name in &names {
+                indices.push(try!(resolve(name, declared, kind)));
+            }
+            object.insert(indices_key.to_string(), Json::Array(indices));
+        } else {
+            return Err(DecoderError::ExpectedError("Array".to_string(), names.to_string()));
+        }
+    }
+    Ok(())
+}
+
+fn indices_to_names(object: &mut Object, indices_key: &str, names_key: &str, declared: &Vec<String>) {
+    let names: Option<Vec<Json>> = object.get(indices_key).and_then(|i| i.as_array()).and_then(|indices| {
+        indices.iter().map(|i| {
+            i.as_u64().and_then(|i| declared.get(i as usize)).map(|name| Json::String(name.clone()))
+        }).collect()
+    });
+    if let Some(names) = names {
+        object.remove(indices_key);
+        object.insert(names_key.to_string(), Json::Array(names));
+    }
+}
+
+fn resolve(name: &Json, declared: &Vec<String>, kind: &str) -> Result<Json, DecoderError> {
+    if let Some(name) = name.as_string() {
+        declared.iter().position(|n| n == name).map(|i| Json::U64(i as u64)).ok_or_else(|| {
+            DecoderError::ApplicationError(format!("Unknown {} name: {}", kind, name))
+        })
+    } else {
+        Err(DecoderError::ExpectedError("String".to_string(), name.to_string()))
+    }
+}
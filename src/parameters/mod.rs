@@ -25,6 +25,13 @@ pub mod order_n;
 use super::model::Vertex;
 use super::model::Model;
 use super::model::VariableIndex;
+use super::model::CompileError;
+use super::model::ParameterIndex;
+use super::formula::CompareOp;
+
+use self::order_0::Order0;
+use self::order_1::Order1;
+use self::order_n::OrderN;
 
 /// Colors represent a set of constrains imposed on the parametric space P.
 /// The form of these constrains is left to the implementation, however,
@@ -49,12 +56,23 @@ pub trait Colors : PartialEq + Sized {
     /// ff = { p | p \in empty_set }
     fn ff() -> Self;
 
+    /// Check whether this implementation can represent all parameter
+    /// constrains that are created from the given model.
+    /// If this is not the case, `divide` fails for some vertices.
+    fn supports(model: &Model) -> Result<(), CompileError>;
+
     /// Divide the parameter space into two areas. One where given
     /// equation is negative and one where it is positive.
-    fn divide(model: &Model, variable_index: &VariableIndex, vertex: &Vertex) -> (Self, Self);
+    /// Fails if the equation can't be represented by this implementation.
+    fn divide(model: &Model, variable_index: &VariableIndex, vertex: &Vertex) -> Result<(Self, Self), CompileError>;
 
     fn model_bounds(model: &Model) -> Self;
 
+    /// Create the constrain sum(c * p) op value from the coefficients of parameters.
+    /// Return None if the constrain cannot be represented by this implementation.
+    fn parameter_constraint(model: &Model, coefficients: &Vec<(ParameterIndex, f64)>, op: &CompareOp, value: f64)
+        -> Option<Self>;
+
     /// A logical disjunction of two parameter constrains.
     /// A or B = { p | p \in A || p \in B }
     fn or(&self, other: &Self) -> Self;
@@ -77,4 +95,31 @@ pub trait Colors : PartialEq + Sized {
 
     fn is_not_empty(&self) -> bool { !self.is_empty() }
 
-}
\ No newline at end of file
+}
+
+//Evaluate a parameter constraint that does not depend on any parameter.
+fn compare(sum: f64, op: &CompareOp, value: f64) -> bool {
+    match op {
+        &CompareOp::LT => sum < value,
+        &CompareOp::GT => sum > value,
+        &CompareOp::LE => sum <= value,
+        &CompareOp::GE => sum >= value
+    }
+}
+
+/// Results of `Colors::supports` for all available Colors implementations.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Capabilities {
+    pub order_0: Result<(), CompileError>,
+    pub order_1: Result<(), CompileError>,
+    pub order_n: Result<(), CompileError>
+}
+
+/// Check which Colors implementations can be used with given compiled model.
+pub fn capabilities(model: &Model) -> Capabilities {
+    Capabilities {
+        order_0: Order0::supports(model),
+        order_1: Order1::supports(model),
+        order_n: OrderN::supports(model)
+    }
+}
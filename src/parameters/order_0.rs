@@ -2,10 +2,14 @@ use std::cmp::PartialOrd;
 use std::cmp::Ordering;
 
 use super::Colors;
+use super::compare;
 
 use super::super::model::Vertex;
 use super::super::model::Model;
 use super::super::model::VariableIndex;
+use super::super::model::CompileError;
+use super::super::model::ParameterIndex;
+use super::super::formula::CompareOp;
 use super::super::model::evaluable::Evaluable2;
 
 ///A variant of Colors that is represented by a formula of inequality polynomials of order 0.
@@ -19,13 +23,22 @@ impl Colors for Order0 {
 
     fn ff() -> Self { Order0(false) }
 
-    fn divide(model: &Model, variable_index: &VariableIndex, vertex: &Vertex) -> (Self, Self) {
+    fn supports(model: &Model) -> Result<(), CompileError> {
+        for v in 0..model.equations.len() {
+            if !model.equation_parameters(&v).is_empty() {
+                return Err(CompileError::Parametrised { variable: v });
+            }
+        }
+        Ok(())
+    }
+
+    fn divide(model: &Model, variable_index: &VariableIndex, vertex: &Vertex) -> Result<(Self, Self), CompileError> {
         let ref equation = model.equations[*variable_index];
         let ref thresholds = model.variables[*variable_index];
         let mut sum = 0.0;
         for summand in equation {
             if !summand.parameter_indices.is_empty() {
-                panic!["Model contains parameters!"]
+                return Err(CompileError::Parametrised { variable: *variable_index });
             } else {
                 let mut result = summand.multiplier;
                 for var in &summand.variable_indices {
@@ -37,13 +50,13 @@ impl Colors for Order0 {
                 sum += result;
             }
         }
-        if sum > 0.0 {
+        Ok(if sum > 0.0 {
             (Order0(false), Order0(true))
         } else if sum < 0.0 {
             (Order0(true), Order0(false))
         } else {
             (Order0(false), Order0(false))
-        }
+        })
     }
 
     fn or(&self, other: &Self) -> Self { Order0(self.0 || other.0) }
@@ -59,6 +72,13 @@ impl Colors for Order0 {
     fn model_bounds(model: &Model) -> Self {
         Order0(true)
     }
+
+    fn parameter_constraint(model: &Model, coefficients: &Vec<(ParameterIndex, f64)>, op: &CompareOp, value: f64)
+        -> Option<Self> {
+        if coefficients.iter().all(|&(_, c)| c == 0.0) {
+            Some(Order0(compare(0.0, op, value)))
+        } else { None }
+    }
 }
 
 impl PartialOrd<Order0> for Order0 {
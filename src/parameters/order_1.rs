@@ -2,8 +2,12 @@ use super::super::model::Vertex;
 use super::super::model::evaluable::Evaluable2;
 use super::super::model::VariableIndex;
 use super::super::model::Model;
+use super::super::model::CompileError;
+use super::super::model::ParameterIndex;
+use super::super::formula::CompareOp;
 use super::super::types::Interval;
 use super::Colors;
+use super::compare;
 use std::cmp::Ordering;
 use std::f64::INFINITY;
 use std::f64::NEG_INFINITY;
@@ -44,14 +48,28 @@ impl Colors for Order1 {
 
     fn ff() -> Self { Order1(vec![]) }
 
+    fn supports(model: &Model) -> Result<(), CompileError> {
+        for (v, equation) in model.equations.iter().enumerate() {
+            if let Some(s) = equation.iter().position(|s| s.parameter_indices.len() > 1) {
+                return Err(CompileError::ParameterProduct { variable: v, summand: s });
+            }
+            if model.equation_parameters(&v).len() > 1 {
+                return Err(CompileError::MultipleParameters { variable: v });
+            }
+        }
+        Ok(())
+    }
 
-    fn divide(model: &Model, variable_index: &VariableIndex, vertex: &Vertex) -> (Self, Self) {
+    fn divide(model: &Model, variable_index: &VariableIndex, vertex: &Vertex) -> Result<(Self, Self), CompileError> {
         let ref equation = model.equations[*variable_index];
         let ref thresholds = model.variables[*variable_index];
         let mut parameter = None;
         let mut derivation = 0.0;
         let mut denominator = 0.0;
-        for summand in equation {
+        for (s, summand) in equation.iter().enumerate() {
+            if summand.parameter_indices.len() > 1 {
+                return Err(CompileError::ParameterProduct { variable: *variable_index, summand: s });
+            }
             let mut result = summand.multiplier;
             for var in &summand.variable_indices {
                 result *= model.variables[*var][vertex[*var]];
@@ -60,8 +78,8 @@ impl Colors for Order1 {
                 result *= f.eval(&model.variables[*var][vertex[*var]]);
             }
             if let Some(index) = summand.parameter_indices.first() {
-                if parameter.is_some() && parameter == Some(index) {
-                    panic!["More than one parameter per equation!"]
+                if parameter.is_some() && parameter != Some(index) {
+                    return Err(CompileError::MultipleParameters { variable: *variable_index });
                 }
                 denominator += result;
                 parameter = Some(index);
@@ -86,13 +104,13 @@ impl Colors for Order1 {
                 lower[*parameter] = Interval(split, INFINITY);
                 upper[*parameter] = Interval(NEG_INFINITY, split);
             }
-            (Order1(vec![Clause(lower)]), Order1(vec![Clause(upper)]))
+            Ok((Order1(vec![Clause(lower)]), Order1(vec![Clause(upper)])))
         } else if derivation > 0.0 {
-            (Self::ff(), Self::tt())
+            Ok((Self::ff(), Self::tt()))
         } else if derivation < 0.0 {
-            (Self::tt(), Self::ff())
+            Ok((Self::tt(), Self::ff()))
         } else {
-            (Self::ff(), Self::ff())
+            Ok((Self::ff(), Self::ff()))
         }
     }
 
@@ -173,6 +191,28 @@ impl Colors for Order1 {
     fn model_bounds(model: &Model) -> Self {
         model.full_order_1_colors()
     }
+
+    //Only constraints of one parameter can be represented as an interval.
+    fn parameter_constraint(model: &Model, coefficients: &Vec<(ParameterIndex, f64)>, op: &CompareOp, value: f64)
+        -> Option<Self> {
+        let coefficients: Vec<&(ParameterIndex, f64)> = coefficients.iter().filter(|&&(_, c)| c != 0.0).collect();
+        match coefficients.len() {
+            0 => Some(if compare(0.0, op, value) { Self::tt() } else { Self::ff() }),
+            1 if coefficients[0].0 < model.parameter_bounds.len() => {
+                let (parameter, c) = *coefficients[0];
+                let bound = value / c;
+                // c * p < value is p < value / c for positive c and p > value / c for negative c
+                let lower = match op {
+                    &CompareOp::LT | &CompareOp::LE => c > 0.0,
+                    &CompareOp::GT | &CompareOp::GE => c < 0.0
+                };
+                let mut clause = vec![Interval::one(); model.parameter_bounds.len()];
+                clause[parameter] = if lower { Interval(NEG_INFINITY, bound) } else { Interval(bound, INFINITY) };
+                Some(Order1(vec![Clause(clause)]))
+            }
+            _ => None
+        }
+    }
 }
 
 impl PartialEq for Order1 {
@@ -321,4 +361,36 @@ impl ToJson for Order1 {
         })*/
         self.0.to_json()
     }
-}
\ No newline at end of file
+}
+
+//Infinite bounds are written as null by ToJson, so null is read back as infinity.
+impl FromJson<Clause> for Clause {
+    fn from_json(json: &Json) -> Result<Clause, DecoderError> {
+        if let &Json::Array(ref intervals) = json {
+            let mut result = vec![];
+            for interval in intervals {
+                match interval.as_array() {
+                    Some(bounds) if bounds.len() == 2 => {
+                        let low = if bounds[0].is_null() { NEG_INFINITY } else { try![f64::from_json(&bounds[0])] };
+                        let high = if bounds[1].is_null() { INFINITY } else { try![f64::from_json(&bounds[1])] };
+                        result.push(Interval(low, high));
+                    }
+                    _ => return Err(DecoderError::ExpectedError("Interval".to_string(), interval.to_string()))
+                }
+            }
+            Ok(Clause(result))
+        } else { Err(DecoderError::ExpectedError("Array".to_string(), json.to_string())) }
+    }
+}
+
+impl FromJson<Order1> for Order1 {
+    fn from_json(json: &Json) -> Result<Order1, DecoderError> {
+        if let &Json::Array(ref clauses) = json {
+            let mut result = vec![];
+            for clause in clauses {
+                result.push(try![Clause::from_json(clause)]);
+            }
+            Ok(Order1(result))
+        } else { Err(DecoderError::ExpectedError("Array".to_string(), json.to_string())) }
+    }
+}
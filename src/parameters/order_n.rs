@@ -2,6 +2,9 @@ use super::super::model::Vertex;
 use super::super::model::VariableIndex;
 use super::super::model::evaluable::Evaluable2;
 use super::super::model::Model;
+use super::super::model::CompileError;
+use super::super::model::ParameterIndex;
+use super::super::formula::CompareOp;
 
 extern crate z3;
 use self::z3::{Context, Ast, Solver};
@@ -53,19 +56,29 @@ impl<'ctx> Colors for OrderN<'ctx> {
         }
     }
 
-    fn divide(model: &Model, variable_index: &VariableIndex, vertex: &Vertex) -> (Self, Self) {
+    fn supports(model: &Model) -> Result<(), CompileError> {
+        for (v, equation) in model.equations.iter().enumerate() {
+            if let Some(s) = equation.iter().position(|s| s.parameter_indices.len() > 1) {
+                return Err(CompileError::ParameterProduct { variable: v, summand: s });
+            }
+        }
+        Ok(())
+    }
+
+    fn divide(model: &Model, variable_index: &VariableIndex, vertex: &Vertex) -> Result<(Self, Self), CompileError> {
         let ref equation = model.equations[*variable_index];
-        //TODO: This is bullshit!
-        let ref thresholds = model.variables[*variable_index];
         let param_count = model.parameter_bounds.len();
         let mut sums = vec![0.0; param_count + 1];
-        for summand in equation {
+        for (s, summand) in equation.iter().enumerate() {
+            if summand.parameter_indices.len() > 1 {
+                return Err(CompileError::ParameterProduct { variable: *variable_index, summand: s });
+            }
             let mut result = summand.multiplier;
             for var in &summand.variable_indices {
-                result *= thresholds[vertex[*var]];
+                result *= model.variables[*var][vertex[*var]];
             }
             for &Evaluable2(ref var, ref f) in &summand.functions {
-                result *= f.eval(&thresholds[vertex[*var]]);
+                result *= f.eval(&model.variables[*var][vertex[*var]]);
             }
             if let Some(index) = summand.parameter_indices.first() {
                 sums[*index] += result;
@@ -73,17 +86,22 @@ impl<'ctx> Colors for OrderN<'ctx> {
                 sums[param_count] += result;
             }
         }
+        let not_representable = CompileError::NotRepresentable { variable: *variable_index };
         unsafe {
-            let constant = sums[param_count];
-            let precision = 1000000;
-            let mut polynom = (*Z3).from_real((constant * precision as f64) as i32, precision);
+            let mut polynom = match real(sums[param_count]) {
+                Some(constant) => constant,
+                None => return Err(not_representable)
+            };
             for i in 0..param_count {
                 let p = (*Z3).numbered_real_const(i as u32);
-                let c = (*Z3).from_real((sums[i] * precision as f64) as i32, precision);
+                let c = match real(sums[i]) {
+                    Some(c) => c,
+                    None => return Err(not_representable)
+                };
                 polynom = polynom.add(&[&p.mul(&[&c])])
             };
             let zero = (*Z3).from_real(0, 1);
-            (OrderN(polynom.lt(&zero)), OrderN(polynom.gt(&zero)))
+            Ok((OrderN(polynom.lt(&zero)), OrderN(polynom.gt(&zero))))
         }
     }
 
@@ -111,6 +129,65 @@ impl<'ctx> Colors for OrderN<'ctx> {
         }
     }
     fn model_bounds(model: &Model) -> Self {
-        unimplemented!()
+        unsafe {
+            model.parameter_bounds.iter().enumerate().fold(Self::tt(), |acc, (i, bounds)| {
+                let p = (*Z3).numbered_real_const(i as u32);
+                //bounds that can't be represented (infinite ones) do not restrict the parameter
+                let acc = match real(bounds.0) {
+                    Some(low) => acc.and(&OrderN(p.gt(&low))),
+                    None => acc
+                };
+                match real(bounds.1) {
+                    Some(high) => acc.and(&OrderN(p.lt(&high))),
+                    None => acc
+                }
+            })
+        }
+    }
+
+    fn parameter_constraint(model: &Model, coefficients: &Vec<(ParameterIndex, f64)>, op: &CompareOp, value: f64)
+        -> Option<Self> {
+        if coefficients.iter().any(|&(p, _)| p >= model.parameter_bounds.len()) {
+            return None;
+        }
+        unsafe {
+            let mut polynom = (*Z3).from_real(0, 1);
+            for &(i, c) in coefficients {
+                let p = (*Z3).numbered_real_const(i as u32);
+                let c = match real(c) {
+                    Some(c) => c,
+                    None => return None
+                };
+                polynom = polynom.add(&[&p.mul(&[&c])])
+            }
+            let value = match real(value) {
+                Some(value) => value,
+                None => return None
+            };
+            Some(OrderN(match op {
+                &CompareOp::LT => polynom.lt(&value),
+                &CompareOp::GT => polynom.gt(&value),
+                &CompareOp::LE => polynom.le(&value),
+                &CompareOp::GE => polynom.ge(&value)
+            }))
+        }
     }
-}
\ No newline at end of file
+}
+
+//A rational with the precision of 10^-6. The numerator is composed from two parts,
+//so unlike a single `from_real` it is not limited to i32.
+//Returns None for values that are not finite or exceed 2^61 * 10^-6.
+unsafe fn real<'ctx>(value: f64) -> Option<Ast<'ctx>> {
+    let precision = 1000000;
+    let scaled = (value * precision as f64).round();
+    if !scaled.is_finite() || scaled.abs() >= (1u64 << 61) as f64 {
+        return None;
+    }
+    let scaled = scaled as i64;
+    let shift = 1i64 << 30;
+    let ctx: &'ctx Context = &*Z3;
+    let numerator = ctx.from_real((scaled / shift) as i32, 1)
+        .mul(&[&ctx.from_real(shift as i32, 1)])
+        .add(&[&ctx.from_real((scaled % shift) as i32, 1)]);
+    Some(numerator.mul(&[&ctx.from_real(1, precision)]))
+}
@@ -1,4 +1,496 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Error;
 
-pub struct OdeModel {
+use rustc_serialize::json::{Json, ToJson, DecoderError};
+
+use super::Summand;
+use super::Evaluable;
+use super::Evaluable::*;
+use super::Point;
+use super::names::declared_names;
+use super::model::VariableIndex;
+use super::polynomial::*;
+
+/// Parser for a human readable equation syntax, for example:
+///
+/// `dx/dt = k1*y*hillp(x, 2.0, 5, 0, 1) - 0.5*x`
+///
+/// Equation is a sum of products of numbers, constants, variables, parameters and functions.
+/// Parentheses are expanded, division is allowed only by constant expressions and `^` only with
+/// non-negative integer exponents. The `dx/dt =` (or just `x =`) prefix is optional.
+///
+/// Supported functions (case insensitive, the first argument is always a variable):
+///
+///  - `hillp(x, theta, n, a, b)`, `hillm(x, theta, n, a, b)` - increasing/decreasing Hill function
+///  - `sigmp(x, theta, k, a, b)`, `sigmm(x, theta, k, a, b)` - increasing/decreasing sigmoid (`sigm` = `sigmp`)
+///  - `step(x, theta, a, b)` - step from a to b at theta
+///  - `ramp(x, low, high, a, b)` - ramp from a to b between low and high
+///  - `approx(x, t1, v1, t2, v2, ...)` - piecewise linear approximation with points (t, v)
+pub struct EquationParser {
+    pub variables: Vec<String>,
+    pub parameters: Vec<String>,
+    pub constants: HashMap<String, f64>
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        write!(f, "line {}, column {}: {}", self.line, self.column, self.message)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+enum Token {
+    Number(f64), Identifier(String),
+    Plus, Minus, Star, Slash, Caret, Comma, Equals, LeftParen, RightParen, End
+}
+
+#[derive(Debug, Clone)]
+struct Positioned {
+    token: Token,
+    line: usize,
+    column: usize
+}
+
+impl EquationParser {
+
+    pub fn new(variables: Vec<String>, parameters: Vec<String>) -> EquationParser {
+        EquationParser { variables: variables, parameters: parameters, constants: HashMap::new() }
+    }
+
+    /// Parse one equation. If the equation has a `dx/dt =` prefix, the index of x is returned too.
+    pub fn parse_equation(&self, text: &str) -> Result<(Option<VariableIndex>, Vec<Summand>), ParseError> {
+        self.parse_equation_at(text, 1, 1)
+    }
+
+    /// Same as `parse_equation`, but error positions are reported relative to
+    /// given line and column where the equation text starts.
+    pub fn parse_equation_at(&self, text: &str, line: usize, column: usize)
+        -> Result<(Option<VariableIndex>, Vec<Summand>), ParseError>
+    {
+        let tokens = try!(tokenize(text, line, column));
+        let mut cursor = Cursor { tokens: &tokens, position: 0, parser: self };
+        let target = try!(cursor.target());
+        let equation = try!(cursor.sum());
+        try!(cursor.expect(Token::End));
+        Ok((target, equation))
+    }
+
+    /// Parse a system of equations, one per line, each with a `dx/dt =` prefix.
+    /// Empty lines are skipped.
+    pub fn parse_system(&self, text: &str) -> Result<Vec<(VariableIndex, Vec<Summand>)>, ParseError> {
+        let mut result = vec![];
+        for (i, line) in text.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            match try!(self.parse_equation_at(line, i + 1, 1)) {
+                (Some(variable), equation) => result.push((variable, equation)),
+                (None, _) => return Err(ParseError {
+                    line: i + 1, column: 1, message: "Expected dx/dt = prefix".to_string()
+                })
+            }
+        }
+        Ok(result)
+    }
+
+    fn resolve(&self, name: &str) -> Option<Vec<Summand>> {
+        if let Some(i) = self.variables.iter().position(|v| v == name) {
+            Some(variable(i))
+        } else if let Some(i) = self.parameters.iter().position(|p| p == name) {
+            Some(parameter(i))
+        } else if let Some(c) = self.constants.get(name) {
+            Some(constant(*c))
+        } else { None }
+    }
 }
 
+struct Cursor<'a> {
+    tokens: &'a Vec<Positioned>,
+    position: usize,
+    parser: &'a EquationParser
+}
+
+impl<'a> Cursor<'a> {
+
+    fn peek(&self) -> &Token {
+        &self.tokens[self.position].token
+    }
+
+    fn peek_at(&self, offset: usize) -> &Token {
+        let i = (self.position + offset).min(self.tokens.len() - 1);
+        &self.tokens[i].token
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.tokens[self.position].token.clone();
+        if token != Token::End {
+            self.position += 1;
+        }
+        token
+    }
+
+    fn error<T>(&self, message: String) -> Result<T, ParseError> {
+        let ref token = self.tokens[self.position];
+        Err(ParseError { line: token.line, column: token.column, message: message })
+    }
+
+    fn expect(&mut self, token: Token) -> Result<(), ParseError> {
+        if *self.peek() == token {
+            self.next();
+            Ok(())
+        } else {
+            let message = format!("Expected {}, found {}", describe(&token), describe(self.peek()));
+            self.error(message)
+        }
+    }
+
+    //Optional `dx/dt =` or `x =` prefix
+    fn target(&mut self) -> Result<Option<VariableIndex>, ParseError> {
+        let name = match (self.peek_at(0).clone(), self.peek_at(1).clone(), self.peek_at(2).clone(), self.peek_at(3).clone()) {
+            (Token::Identifier(ref dx), Token::Slash, Token::Identifier(ref dt), Token::Equals)
+                if dx.starts_with("d") && dt == "dt" => {
+                self.position += 3;
+                dx[1..].to_string()
+            }
+            (Token::Identifier(ref x), Token::Equals, _, _) => {
+                self.position += 1;
+                x.clone()
+            }
+            _ => return Ok(None)
+        };
+        if let Some(i) = self.parser.variables.iter().position(|v| *v == name) {
+            self.next();
+            Ok(Some(i))
+        } else {
+            self.error(format!("Unknown variable: {}", name))
+        }
+    }
+
+    fn sum(&mut self) -> Result<Vec<Summand>, ParseError> {
+        let mut result = try!(self.term());
+        loop {
+            match *self.peek() {
+                Token::Plus => {
+                    self.next();
+                    result.extend(try!(self.term()));
+                }
+                Token::Minus => {
+                    self.next();
+                    result.extend(negate(try!(self.term())));
+                }
+                _ => return Ok(result)
+            }
+        }
+    }
+
+    fn term(&mut self) -> Result<Vec<Summand>, ParseError> {
+        let mut result = try!(self.unary());
+        loop {
+            match *self.peek() {
+                Token::Star => {
+                    self.next();
+                    let right = try!(self.unary());
+                    result = multiply(&result, &right);
+                }
+                Token::Slash => {
+                    self.next();
+                    let divisor = try!(self.constant_value("Division by a non-constant expression"));
+                    if divisor == 0.0 {
+                        return self.error("Division by zero".to_string());
+                    }
+                    result = multiply(&result, &constant(1.0 / divisor));
+                }
+                _ => return Ok(result)
+            }
+        }
+    }
+
+    fn unary(&mut self) -> Result<Vec<Summand>, ParseError> {
+        match *self.peek() {
+            Token::Minus => {
+                self.next();
+                Ok(negate(try!(self.unary())))
+            }
+            Token::Plus => {
+                self.next();
+                self.unary()
+            }
+            _ => self.power()
+        }
+    }
+
+    fn power(&mut self) -> Result<Vec<Summand>, ParseError> {
+        let base = try!(self.atom());
+        if *self.peek() == Token::Caret {
+            self.next();
+            let exponent = try!(self.constant_value("Exponent must be a constant"));
+            if exponent < 0.0 || exponent.fract() != 0.0 {
+                return self.error(format!("Exponent must be a non-negative integer, found {}", exponent));
+            }
+            Ok((0..(exponent as usize)).fold(constant(1.0), |acc, _| multiply(&acc, &base)))
+        } else {
+            Ok(base)
+        }
+    }
+
+    fn atom(&mut self) -> Result<Vec<Summand>, ParseError> {
+        match self.peek().clone() {
+            Token::Number(n) => {
+                self.next();
+                Ok(constant(n))
+            }
+            Token::LeftParen => {
+                self.next();
+                let inner = try!(self.sum());
+                try!(self.expect(Token::RightParen));
+                Ok(inner)
+            }
+            Token::Identifier(name) => {
+                if *self.peek_at(1) == Token::LeftParen {
+                    self.function(&name)
+                } else if let Some(value) = self.parser.resolve(&name) {
+                    self.next();
+                    Ok(value)
+                } else {
+                    self.error(format!("Unknown identifier: {}", name))
+                }
+            }
+            other => self.error(format!("Unexpected {}", describe(&other)))
+        }
+    }
+
+    //Parse a unary expression which must evaluate to a constant (no variables, parameters or functions).
+    fn constant_value(&mut self, message: &str) -> Result<f64, ParseError> {
+        let start = self.position;
+        let value = try!(self.unary());
+        as_constant(&value).map(Ok).unwrap_or_else(|| {
+            self.position = start;
+            self.error(message.to_string())
+        })
+    }
+
+    fn function(&mut self, name: &str) -> Result<Vec<Summand>, ParseError> {
+        let name: &str = &name.to_lowercase();
+        let start = self.position;
+        self.next();    //name
+        self.next();    //(
+        let variable = match self.peek().clone() {
+            Token::Identifier(ref v) if self.parser.variables.contains(v) => {
+                self.next();
+                self.parser.variables.iter().position(|i| i == v).unwrap()
+            }
+            _ => return self.error(format!("Expected variable as the first argument of {}", name))
+        };
+        let mut args = vec![];
+        while *self.peek() == Token::Comma {
+            self.next();
+            let arg = try!(self.sum());
+            match as_constant(&arg) {
+                Some(value) => args.push(value),
+                None => return self.error(format!("Arguments of {} must be constant", name))
+            }
+        }
+        try!(self.expect(Token::RightParen));
+        let evaluable = match (name, args.len()) {
+            ("hillp", 4) => Hill { variable_index: variable, theta: args[0], n: args[1], a: args[2], b: args[3] },
+            ("hillm", 4) => Hill { variable_index: variable, theta: args[0], n: args[1], a: args[3], b: args[2] },
+            ("sigm", 4) | ("sigmp", 4) =>
+                Sigmoid { variable_index: variable, theta: args[0], k: args[1], a: args[2], b: args[3] },
+            ("sigmm", 4) => Sigmoid { variable_index: variable, theta: args[0], k: args[1], a: args[3], b: args[2] },
+            ("step", 3) => Step { variable_index: variable, theta: args[0], a: args[1], b: args[2] },
+            ("ramp", 4) => Ramp { variable_index: variable, low: args[0], high: args[1], a: args[2], b: args[3] },
+            ("approx", n) if n > 0 && n % 2 == 0 => RampApproximation {
+                variable_index: variable,
+                approximation: args.chunks(2).map(|p| Point { threshold: p[0], value: p[1] }).collect()
+            },
+            ("hillp", _) | ("hillm", _) | ("sigm", _) | ("sigmp", _) | ("sigmm", _) | ("ramp", _) => {
+                self.position = start;
+                return self.error(format!("Function {} expects 5 arguments", name));
+            }
+            ("step", _) => {
+                self.position = start;
+                return self.error(format!("Function {} expects 4 arguments", name));
+            }
+            ("approx", _) => {
+                self.position = start;
+                return self.error(format!("Function {} expects a variable and pairs of points", name));
+            }
+            _ => {
+                self.position = start;
+                return self.error(format!("Unknown function: {}", name));
+            }
+        };
+        Ok(vec![Summand { constant: 1.0, variable_indices: vec![], parameter_indices: vec![], evaluables: vec![evaluable] }])
+    }
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        &Token::Number(n) => format!("number {}", n),
+        &Token::Identifier(ref name) => format!("identifier {}", name),
+        &Token::End => "end of equation".to_string(),
+        &Token::Plus => "'+'".to_string(),
+        &Token::Minus => "'-'".to_string(),
+        &Token::Star => "'*'".to_string(),
+        &Token::Slash => "'/'".to_string(),
+        &Token::Caret => "'^'".to_string(),
+        &Token::Comma => "','".to_string(),
+        &Token::Equals => "'='".to_string(),
+        &Token::LeftParen => "'('".to_string(),
+        &Token::RightParen => "')'".to_string()
+    }
+}
+
+fn tokenize(text: &str, line: usize, first_column: usize) -> Result<Vec<Positioned>, ParseError> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut result = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let column = first_column + i;
+        let simple = match c {
+            '+' => Some(Token::Plus), '-' => Some(Token::Minus),
+            '*' => Some(Token::Star), '/' => Some(Token::Slash),
+            '^' => Some(Token::Caret), ',' => Some(Token::Comma),
+            '=' => Some(Token::Equals),
+            '(' => Some(Token::LeftParen), ')' => Some(Token::RightParen),
+            _ => None
+        };
+        if let Some(token) = simple {
+            result.push(Positioned { token: token, line: line, column: column });
+            i += 1;
+        } else if c.is_whitespace() {
+            i += 1;
+        } else if c.is_digit(10) || c == '.' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_digit(10) || chars[i] == '.') {
+                i += 1;
+            }
+            //exponent
+            if i < chars.len() && (chars[i] == 'e' || chars[i] == 'E') {
+                let mut j = i + 1;
+                if j < chars.len() && (chars[j] == '+' || chars[j] == '-') {
+                    j += 1;
+                }
+                if j < chars.len() && chars[j].is_digit(10) {
+                    i = j;
+                    while i < chars.len() && chars[i].is_digit(10) {
+                        i += 1;
+                    }
+                }
+            }
+            let number: String = chars[start..i].iter().cloned().collect();
+            match number.parse::<f64>() {
+                Ok(n) => result.push(Positioned { token: Token::Number(n), line: line, column: column }),
+                Err(_) => return Err(ParseError {
+                    line: line, column: column, message: format!("Invalid number: {}", number)
+                })
+            }
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            let name: String = chars[start..i].iter().cloned().collect();
+            result.push(Positioned { token: Token::Identifier(name), line: line, column: column });
+        } else {
+            return Err(ParseError { line: line, column: column, message: format!("Unexpected character: {}", c) });
+        }
+    }
+    result.push(Positioned { token: Token::End, line: line, column: first_column + chars.len() });
+    Ok(result)
+}
+
+/// Write equation in the syntax accepted by `EquationParser` (without the `dx/dt =` prefix).
+pub fn write_equation(equation: &[Summand], variables: &[String], parameters: &[String]) -> String {
+    if equation.is_empty() {
+        return "0".to_string();
+    }
+    let mut result = String::new();
+    for (i, summand) in equation.iter().enumerate() {
+        let negative = summand.constant < 0.0;
+        let mut factors: Vec<String> = vec![];
+        let absolute = summand.constant.abs();
+        if absolute != 1.0 || (summand.variable_indices.is_empty() &&
+            summand.parameter_indices.is_empty() && summand.evaluables.is_empty()) {
+            factors.push(format!("{}", absolute));
+        }
+        factors.extend(summand.variable_indices.iter().map(|v| variables[*v].clone()));
+        factors.extend(summand.parameter_indices.iter().map(|p| parameters[*p].clone()));
+        factors.extend(summand.evaluables.iter().map(|e| write_evaluable(e, variables)));
+        result.push_str(match (i, negative) {
+            (0, true) => "-", (0, false) => "", (_, true) => " - ", (_, false) => " + "
+        });
+        result.push_str(&factors.join("*"));
+    }
+    result
+}
+
+fn write_evaluable(evaluable: &Evaluable, variables: &[String]) -> String {
+    match evaluable {
+        &Hill { variable_index, theta, n, a, b } =>
+            format!("hillp({}, {}, {}, {}, {})", variables[variable_index], theta, n, a, b),
+        &Sigmoid { variable_index, theta, k, a, b } =>
+            format!("sigmp({}, {}, {}, {}, {})", variables[variable_index], theta, k, a, b),
+        &Step { variable_index, theta, a, b } =>
+            format!("step({}, {}, {}, {})", variables[variable_index], theta, a, b),
+        &Ramp { variable_index, low, high, a, b } =>
+            format!("ramp({}, {}, {}, {}, {})", variables[variable_index], low, high, a, b),
+        &RampApproximation { variable_index, ref approximation } => {
+            let points: Vec<String> = approximation.iter().map(|p| {
+                format!("{}, {}", p.threshold, p.value)
+            }).collect();
+            format!("approx({}, {})", variables[variable_index], points.join(", "))
+        }
+    }
+}
+
+/// Replace the "equationText" field of every variable in a JSON model with
+/// the parsed "equation" field.
+pub fn expand_equation_text(json: &Json) -> Result<Json, DecoderError> {
+    let parser = EquationParser::new(declared_names(json, "variables"), declared_names(json, "parameters"));
+    let mut result = json.clone();
+    if let &mut Json::Object(ref mut model) = &mut result {
+        if let Some(&mut Json::Array(ref mut variables)) = model.get_mut("variables") {
+            for (i, variable) in variables.iter_mut().enumerate() {
+                if let &mut Json::Object(ref mut variable) = variable {
+                    if let Some(text) = variable.remove("equationText") {
+                        if variable.contains_key("equation") {
+                            return Err(DecoderError::ApplicationError(
+                                format!("variables[{}] contains both equation and equationText", i)
+                            ));
+                        }
+                        let text = try!(text.as_string().ok_or_else(|| {
+                            DecoderError::ExpectedError("String".to_string(), text.to_string())
+                        }));
+                        match parser.parse_equation(text) {
+                            Ok((Some(target), _)) if target != i => {
+                                return Err(DecoderError::ApplicationError(
+                                    format!("variables[{}].equationText: defines equation of {}", i, parser.variables[target])
+                                ));
+                            }
+                            Ok((_, equation)) => {
+                                variable.insert("equation".to_string(), equation.to_json());
+                            }
+                            Err(error) => {
+                                return Err(DecoderError::ApplicationError(
+                                    format!("variables[{}].equationText: {}", i, error)
+                                ));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+    Ok(result)
+}
@@ -0,0 +1,42 @@
+use super::Summand;
+
+// Equations are kept as sums of summands (polynomials). These helpers are shared
+// by the equation parser and the model importers.
+
+pub fn constant(value: f64) -> Vec<Summand> {
+    vec![Summand { constant: value, variable_indices: vec![], parameter_indices: vec![], evaluables: vec![] }]
+}
+
+pub fn variable(index: usize) -> Vec<Summand> {
+    vec![Summand { constant: 1.0, variable_indices: vec![index], parameter_indices: vec![], evaluables: vec![] }]
+}
+
+pub fn parameter(index: usize) -> Vec<Summand> {
+    vec![Summand { constant: 1.0, variable_indices: vec![], parameter_indices: vec![index], evaluables: vec![] }]
+}
+
+/// If the polynomial contains no variables, parameters or evaluables, return its value.
+pub fn as_constant(value: &Vec<Summand>) -> Option<f64> {
+    if value.iter().all(|s| s.variable_indices.is_empty() && s.parameter_indices.is_empty() && s.evaluables.is_empty()) {
+        Some(value.iter().fold(0.0, |acc, s| acc + s.constant))
+    } else { None }
+}
+
+pub fn negate(value: Vec<Summand>) -> Vec<Summand> {
+    value.into_iter().map(|s| Summand { constant: -s.constant, .. s }).collect()
+}
+
+pub fn multiply(left: &Vec<Summand>, right: &Vec<Summand>) -> Vec<Summand> {
+    let mut result = vec![];
+    for l in left {
+        for r in right {
+            let mut product = l.clone();
+            product.constant *= r.constant;
+            product.variable_indices.extend(r.variable_indices.iter().cloned());
+            product.parameter_indices.extend(r.parameter_indices.iter().cloned());
+            product.evaluables.extend(r.evaluables.iter().cloned());
+            result.push(product);
+        }
+    }
+    result
+}
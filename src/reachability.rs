@@ -0,0 +1,119 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
+
+use super::checker::CheckerContext;
+use super::generator::StateSet2;
+use super::generator::compute_directed_edges;
+use super::model::StateID;
+use super::model::TimeFlow;
+use super::parameters::Colors;
+
+/// Settings of a breadth first reachability search.
+pub struct ReachOptions<'a, C: Colors + 'a> {
+    /// Successors (true) or predecessors (false) are explored.
+    pub time_flow: TimeFlow,
+    /// Only these states and colors can be visited (the initial states are always included).
+    pub within: Option<&'a StateSet2<C>>,
+    /// The search stops once this set is reached for all colors of the initial set.
+    pub target: Option<&'a StateSet2<C>>,
+    /// Maximal number of BFS layers to explore.
+    pub max_depth: Option<usize>
+}
+
+impl<'a, C: Colors + 'a> ReachOptions<'a, C> {
+    pub fn forward() -> ReachOptions<'a, C> {
+        ReachOptions { time_flow: true, within: None, target: None, max_depth: None }
+    }
+
+    pub fn backward() -> ReachOptions<'a, C> {
+        ReachOptions { time_flow: false, within: None, target: None, max_depth: None }
+    }
+}
+
+/// Result of a reachability search.
+#[derive(Debug, Clone)]
+pub struct Reachability<C: Colors> {
+    /// Visited states with the colors for which they were reached.
+    pub states: StateSet2<C>,
+    /// Number of explored BFS layers.
+    pub depth: usize,
+    /// Colors for which the target set was reached (ff if there is no target).
+    pub hit: C
+}
+
+/// States reachable from the initial set.
+pub fn forward_reachable<C: Colors + Debug + Clone>(ctx: &mut CheckerContext<C>, initial: StateSet2<C>) -> StateSet2<C> {
+    reachable(ctx, initial, &ReachOptions::forward(), |_, _| {}).states
+}
+
+/// States from which the initial set is reachable.
+pub fn backward_reachable<C: Colors + Debug + Clone>(ctx: &mut CheckerContext<C>, initial: StateSet2<C>) -> StateSet2<C> {
+    reachable(ctx, initial, &ReachOptions::backward(), |_, _| {}).states
+}
+
+/// Breadth first search from the initial set. The callback is called with the depth
+/// and the newly reached states and colors of every layer, starting with the initial set.
+pub fn reachable<'a, C, F>(
+    ctx: &mut CheckerContext<C>, initial: StateSet2<C>, options: &ReachOptions<'a, C>, mut callback: F
+) -> Reachability<C>
+    where C: Colors + Debug + Clone + 'a, F: FnMut(usize, &StateSet2<C>)
+{
+    let reached: StateSet2<C> = initial.into_iter().filter(|&(_, ref colors)| !colors.is_empty()).collect();
+    let all_colors = reached.values().fold(C::ff(), |acc, c| acc.or(c));
+    let mut result = Reachability { states: reached.clone(), depth: 0, hit: C::ff() };
+    let mut frontier = reached;
+    loop {
+        callback(result.depth, &frontier);
+        if let Some(target) = options.target {
+            for (state, colors) in &frontier {
+                if let Some(target_colors) = target.get(state) {
+                    result.hit = result.hit.or(&colors.and(target_colors));
+                }
+            }
+            if all_colors.and(&result.hit.not()).is_empty() {
+                break;
+            }
+        }
+        if options.max_depth.map_or(false, |max| result.depth >= max) {
+            break;
+        }
+        let mut next: StateSet2<C> = HashMap::new();
+        let mut states: Vec<StateID> = frontier.keys().cloned().collect();
+        states.sort();
+        for state in &states {
+            for (successor, edge_colors) in compute_directed_edges::<C>(ctx, state, &options.time_flow) {
+                let pushed = match options.within {
+                    Some(within) => match within.get(&successor) {
+                        Some(allowed) => edge_colors.and(&frontier[state]).and(allowed),
+                        None => continue
+                    },
+                    None => edge_colors.and(&frontier[state])
+                };
+                let new = match result.states.get(&successor) {
+                    Some(old) => pushed.and(&old.not()),
+                    None => pushed
+                };
+                if !new.is_empty() {
+                    let union = match next.get(&successor) {
+                        Some(old) => old.or(&new),
+                        None => new
+                    };
+                    next.insert(successor, union);
+                }
+            }
+        }
+        if next.is_empty() {
+            break;
+        }
+        for (state, colors) in &next {
+            let union = match result.states.get(state) {
+                Some(old) => old.or(colors),
+                None => colors.clone()
+            };
+            result.states.insert(*state, union);
+        }
+        result.depth += 1;
+        frontier = next;
+    }
+    result
+}
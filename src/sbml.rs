@@ -0,0 +1,430 @@
+extern crate xml;
+
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Error;
+
+use self::xml::reader::{EventReader, XmlEvent};
+
+use super::OdeModel;
+use super::Variable;
+use super::Parameter;
+use super::Range;
+use super::Summand;
+use super::Evaluable;
+use super::polynomial::*;
+
+// Import of SBML (Level 2 and 3) models. Every species which is not a boundary condition
+// or constant becomes one variable and every reaction adds its kinetic law (multiplied
+// by the stoichiometry) to the equations of its reactants and products. Kinetic laws describe
+// the change of substance amount, so for species measured in concentrations the contribution
+// is divided by the size of the species compartment (unless hasOnlySubstanceUnits is set).
+//
+// Kinetic laws must be sums of products of numbers, species, parameters and compartments,
+// with division allowed only by constants or by Hill/Michaelis-Menten denominators
+// of the form K^n + S^n (K + S). Such fractions are translated into Hill evaluables.
+// Everything else (function definitions, rules, events, exp, ...) is reported as unsupported.
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum SbmlError {
+    /// The document is not a well formed XML.
+    Xml(String),
+    /// A required element or attribute is missing.
+    Missing(String),
+    /// Identifier used in a kinetic law is not defined.
+    UnknownIdentifier(String),
+    /// The model uses a construct that can't be represented as an OdeModel.
+    Unsupported(String)
+}
+
+impl Display for SbmlError {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        match self {
+            &SbmlError::Xml(ref message) => write!(f, "Invalid XML: {}", message),
+            &SbmlError::Missing(ref what) => write!(f, "Missing {}", what),
+            &SbmlError::UnknownIdentifier(ref id) => write!(f, "Unknown identifier: {}", id),
+            &SbmlError::Unsupported(ref what) => write!(f, "Unsupported: {}", what)
+        }
+    }
+}
+
+/// Import an SBML model.
+///
+/// Global SBML parameters listed in `free_parameters` (by id) become model parameters with
+/// given ranges, all other parameters are replaced by their values. Every variable is
+/// assigned the given range, with thresholds only at its bounds.
+pub fn import_sbml(xml: &str, free_parameters: &[Parameter], range: &Range) -> Result<OdeModel, SbmlError> {
+    let document = try!(parse_xml(xml));
+    let model = try!(document.child("model").ok_or(SbmlError::Missing("model element".to_string())));
+    for unsupported in &["listOfFunctionDefinitions", "listOfRules", "listOfEvents", "listOfInitialAssignments"] {
+        if model.child(unsupported).map(|l| !l.children.is_empty()).unwrap_or(false) {
+            return Err(SbmlError::Unsupported(unsupported.to_string()));
+        }
+    }
+    let mut scope = Scope {
+        variables: vec![],
+        parameters: free_parameters.iter().map(|p| p.name.clone()).collect(),
+        constants: HashMap::new()
+    };
+    let mut sizes: HashMap<String, f64> = HashMap::new();
+    for compartment in model.list("listOfCompartments", "compartment") {
+        let id = try!(compartment.id());
+        let size = try!(compartment.number_or("size", 1.0));
+        sizes.insert(id.clone(), size);
+        scope.constants.insert(id, size);
+    }
+    //volume by which the reaction rates are divided for every variable
+    let mut volumes: Vec<f64> = vec![];
+    for species in model.list("listOfSpecies", "species") {
+        let id = try!(species.id());
+        if species.attribute("boundaryCondition") == Some("true") || species.attribute("constant") == Some("true") {
+            let value = try!(species.number_or("initialConcentration",
+                try!(species.number_or("initialAmount", 0.0))));
+            scope.constants.insert(id, value);
+        } else {
+            let compartment = try!(species.attribute("compartment").ok_or_else(|| {
+                SbmlError::Missing(format!("compartment of {}", id))
+            }));
+            let size = try!(sizes.get(compartment).cloned().ok_or_else(|| {
+                SbmlError::UnknownIdentifier(compartment.to_string())
+            }));
+            if species.attribute("hasOnlySubstanceUnits") == Some("true") {
+                volumes.push(1.0);
+            } else if size == 0.0 {
+                return Err(SbmlError::Unsupported(format!("concentration of {} in a compartment of size 0", id)));
+            } else {
+                volumes.push(size);
+            }
+            scope.variables.push(id);
+        }
+    }
+    for parameter in model.list("listOfParameters", "parameter") {
+        let id = try!(parameter.id());
+        if !scope.parameters.contains(&id) {
+            let value = try!(parameter.number(&id, "value"));
+            scope.constants.insert(id, value);
+        }
+    }
+    let mut equations: Vec<Vec<Summand>> = vec![vec![]; scope.variables.len()];
+    for reaction in model.list("listOfReactions", "reaction") {
+        let id = try!(reaction.id());
+        let law = try!(reaction.child("kineticLaw").ok_or_else(|| {
+            SbmlError::Missing(format!("kinetic law of reaction {}", id))
+        }));
+        let mut local = scope.clone();
+        for list in &[("listOfParameters", "parameter"), ("listOfLocalParameters", "localParameter")] {
+            for parameter in law.list(list.0, list.1) {
+                let p_id = try!(parameter.id());
+                let value = try!(parameter.number(&p_id, "value"));
+                local.constants.insert(p_id, value);
+            }
+        }
+        let math = try!(law.child("math").and_then(|m| m.children.first()).ok_or_else(|| {
+            SbmlError::Missing(format!("math of reaction {}", id))
+        }));
+        let rate = try!(local.convert(&try!(to_expression(math))));
+        for &(list, sign) in &[("listOfReactants", -1.0), ("listOfProducts", 1.0)] {
+            for reference in reaction.list(list, "speciesReference") {
+                let species = try!(reference.attribute("species").ok_or_else(|| {
+                    SbmlError::Missing(format!("species of a reference in reaction {}", id))
+                }));
+                if reference.child("stoichiometryMath").is_some() {
+                    return Err(SbmlError::Unsupported(format!("stoichiometryMath in reaction {}", id)));
+                }
+                let stoichiometry = try!(reference.number_or("stoichiometry", 1.0));
+                if let Some(v) = scope.variables.iter().position(|v| v == species) {
+                    equations[v].extend(rate.iter().map(|s| {
+                        Summand { constant: s.constant * sign * stoichiometry / volumes[v], .. s.clone() }
+                    }));
+                }
+            }
+        }
+    }
+    Ok(OdeModel {
+        name: model.attribute("name").or(model.attribute("id")).unwrap_or("").to_string(),
+        variables: scope.variables.iter().zip(equations.into_iter()).map(|(id, equation)| {
+            Variable {
+                name: id.clone(),
+                range: range.clone(),
+                thresholds: vec![range.min, range.max],
+                var_points: None,
+                equation: equation
+            }
+        }).collect(),
+        parameters: free_parameters.to_vec()
+    })
+}
+
+// ============================= XML ===============================================================
+
+struct Element {
+    name: String,
+    attributes: HashMap<String, String>,
+    children: Vec<Element>,
+    texts: Vec<String>
+}
+
+impl Element {
+
+    fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|c| c.name == name)
+    }
+
+    //All children with given name of the child list with given name.
+    fn list(&self, list: &str, name: &str) -> Vec<&Element> {
+        self.child(list).map(|l| l.children.iter().filter(|c| c.name == name).collect()).unwrap_or(vec![])
+    }
+
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes.get(name).map(|v| v.as_ref())
+    }
+
+    fn id(&self) -> Result<String, SbmlError> {
+        self.attribute("id").map(|i| i.to_string()).ok_or_else(|| {
+            SbmlError::Missing(format!("id of {}", self.name))
+        })
+    }
+
+    fn number(&self, id: &str, attribute: &str) -> Result<f64, SbmlError> {
+        match self.attribute(attribute) {
+            Some(_) => self.number_or(attribute, 0.0),
+            None => Err(SbmlError::Missing(format!("{} of {}", attribute, id)))
+        }
+    }
+
+    fn number_or(&self, attribute: &str, default: f64) -> Result<f64, SbmlError> {
+        match self.attribute(attribute) {
+            Some(value) => value.trim().parse::<f64>().map_err(|_| {
+                SbmlError::Unsupported(format!("{} value {}", attribute, value))
+            }),
+            None => Ok(default)
+        }
+    }
+
+    fn text(&self) -> String {
+        self.texts.concat().trim().to_string()
+    }
+}
+
+//Parse the document into a tree of elements and return the root element.
+fn parse_xml(xml: &str) -> Result<Element, SbmlError> {
+    let mut stack = vec![Element {
+        name: "".to_string(), attributes: HashMap::new(), children: vec![], texts: vec![]
+    }];
+    for event in EventReader::from_str(xml) {
+        match try!(event.map_err(|e| SbmlError::Xml(e.to_string()))) {
+            XmlEvent::StartElement { name, attributes, .. } => {
+                stack.push(Element {
+                    name: name.local_name,
+                    attributes: attributes.into_iter().map(|a| (a.name.local_name, a.value)).collect(),
+                    children: vec![],
+                    texts: vec![]
+                });
+            }
+            XmlEvent::EndElement { .. } => {
+                let element = stack.pop().unwrap();
+                stack.last_mut().unwrap().children.push(element);
+            }
+            XmlEvent::Characters(text) => stack.last_mut().unwrap().texts.push(text),
+            _ => {}
+        }
+    }
+    stack.pop().and_then(|mut document| document.children.pop()).ok_or_else(|| {
+        SbmlError::Missing("sbml element".to_string())
+    })
+}
+
+// ============================= MathML ============================================================
+
+#[derive(Debug, PartialEq, Clone)]
+enum Expression {
+    Number(f64),
+    Identifier(String),
+    Apply(String, Vec<Expression>)
+}
+
+fn to_expression(math: &Element) -> Result<Expression, SbmlError> {
+    match math.name.as_ref() {
+        "cn" => {
+            let parsed = if math.attribute("type") == Some("e-notation") && math.texts.len() == 2 {
+                format!("{}e{}", math.texts[0].trim(), math.texts[1].trim()).parse::<f64>()
+            } else {
+                math.text().parse::<f64>()
+            };
+            parsed.map(Expression::Number).map_err(|_| {
+                SbmlError::Unsupported(format!("number {}", math.text()))
+            })
+        }
+        "ci" => Ok(Expression::Identifier(math.text())),
+        "apply" => {
+            let operator = try!(math.children.first().ok_or_else(|| {
+                SbmlError::Missing("operator of apply".to_string())
+            }));
+            if operator.name == "ci" {
+                return Err(SbmlError::Unsupported(format!("function call {}", operator.text())));
+            }
+            let mut args = vec![];
+            for arg in &math.children[1..] {
+                args.push(try!(to_expression(arg)));
+            }
+            Ok(Expression::Apply(operator.name.clone(), args))
+        }
+        other => Err(SbmlError::Unsupported(format!("MathML element {}", other)))
+    }
+}
+
+// ============================= Conversion ========================================================
+
+#[derive(Clone)]
+struct Scope {
+    variables: Vec<String>,
+    parameters: Vec<String>,
+    constants: HashMap<String, f64>
+}
+
+impl Scope {
+
+    fn convert(&self, expression: &Expression) -> Result<Vec<Summand>, SbmlError> {
+        match expression {
+            &Expression::Number(n) => Ok(constant(n)),
+            &Expression::Identifier(ref id) => {
+                //local parameters of a kinetic law shadow everything else, and global ids are unique,
+                //so constants can be resolved first
+                if let Some(value) = self.constants.get(id) {
+                    Ok(constant(*value))
+                } else if let Some(i) = self.variables.iter().position(|v| v == id) {
+                    Ok(variable(i))
+                } else if let Some(i) = self.parameters.iter().position(|p| p == id) {
+                    Ok(parameter(i))
+                } else {
+                    Err(SbmlError::UnknownIdentifier(id.clone()))
+                }
+            }
+            &Expression::Apply(ref operator, ref args) => {
+                match (operator.as_ref(), args.len()) {
+                    ("plus", _) => {
+                        let mut result = vec![];
+                        for arg in args {
+                            result.extend(try!(self.convert(arg)));
+                        }
+                        Ok(result)
+                    }
+                    ("times", _) => {
+                        let mut result = constant(1.0);
+                        for arg in args {
+                            result = multiply(&result, &try!(self.convert(arg)));
+                        }
+                        Ok(result)
+                    }
+                    ("minus", 1) => Ok(negate(try!(self.convert(&args[0])))),
+                    ("minus", 2) => {
+                        let mut result = try!(self.convert(&args[0]));
+                        result.extend(negate(try!(self.convert(&args[1]))));
+                        Ok(result)
+                    }
+                    ("power", 2) => {
+                        let base = try!(self.convert(&args[0]));
+                        match try!(self.convert(&args[1]).map(|e| as_constant(&e))) {
+                            Some(e) if e >= 0.0 && e.fract() == 0.0 => {
+                                Ok((0..(e as usize)).fold(constant(1.0), |acc, _| multiply(&acc, &base)))
+                            }
+                            _ => match as_constant(&base) {
+                                Some(b) => {
+                                    let e = try!(self.constant_value(&args[1]));
+                                    Ok(constant(b.powf(e)))
+                                }
+                                None => Err(SbmlError::Unsupported("non-integer power of a species".to_string()))
+                            }
+                        }
+                    }
+                    ("divide", 2) => self.divide(&args[0], &args[1]),
+                    (other, _) => Err(SbmlError::Unsupported(format!("operator {} with {} arguments", other, args.len())))
+                }
+            }
+        }
+    }
+
+    fn constant_value(&self, expression: &Expression) -> Result<f64, SbmlError> {
+        as_constant(&try!(self.convert(expression))).ok_or_else(|| {
+            SbmlError::Unsupported(format!("non-constant expression {:?}", expression))
+        })
+    }
+
+    //Species power S^n in the expression, where n is a constant.
+    fn species_power(&self, expression: &Expression) -> Option<(usize, f64)> {
+        match expression {
+            &Expression::Identifier(ref id) => self.variables.iter().position(|v| v == id).map(|i| (i, 1.0)),
+            &Expression::Apply(ref operator, ref args) if operator == "power" && args.len() == 2 => {
+                self.species_power(&args[0]).and_then(|(i, n)| {
+                    if n != 1.0 { return None; }
+                    self.constant_value(&args[1]).ok().map(|e| (i, e))
+                })
+            }
+            _ => None
+        }
+    }
+
+    fn divide(&self, numerator: &Expression, denominator: &Expression) -> Result<Vec<Summand>, SbmlError> {
+        if let Ok(d) = self.constant_value(denominator) {
+            if d == 0.0 {
+                return Err(SbmlError::Unsupported("division by zero".to_string()));
+            }
+            return Ok(multiply(&try!(self.convert(numerator)), &constant(1.0 / d)));
+        }
+        //Hill denominator: K^n + S^n or S^n + K^n
+        let hill = if let &Expression::Apply(ref operator, ref args) = denominator {
+            if operator == "plus" && args.len() == 2 {
+                let first = self.species_power(&args[0]).map(|s| (s, &args[1]));
+                let second = self.species_power(&args[1]).map(|s| (s, &args[0]));
+                first.or(second).and_then(|((species, n), other)| {
+                    self.constant_value(other).ok().map(|k| (species, n, k))
+                })
+            } else { None }
+        } else { None };
+        if let Some((species, n, k)) = hill {
+            if k <= 0.0 || n <= 0.0 {
+                return Err(SbmlError::Unsupported(format!("Hill denominator with K^n = {}, n = {}", k, n)));
+            }
+            let theta = k.powf(1.0 / n);
+            let power = if n == 1.0 {
+                Expression::Identifier(self.variables[species].clone())
+            } else {
+                Expression::Apply("power".to_string(), vec![
+                    Expression::Identifier(self.variables[species].clone()), Expression::Number(n)
+                ])
+            };
+            // S^n * rest / (K^n + S^n) = rest * hill+, otherwise P / (K^n + S^n) = P / K^n * hill-
+            let (rest, evaluable) = if let Some(rest) = remove_factor(numerator, &power, self) {
+                (try!(self.convert(&rest)), Evaluable::Hill { variable_index: species, theta: theta, n: n, a: 0.0, b: 1.0 })
+            } else {
+                (multiply(&try!(self.convert(numerator)), &constant(1.0 / k)),
+                    Evaluable::Hill { variable_index: species, theta: theta, n: n, a: 1.0, b: 0.0 })
+            };
+            Ok(rest.into_iter().map(|mut s| {
+                s.evaluables.push(evaluable.clone());
+                s
+            }).collect())
+        } else {
+            Err(SbmlError::Unsupported(format!("division by {:?}", denominator)))
+        }
+    }
+}
+
+//Remove given factor from a product (or the whole expression if equal).
+fn remove_factor(expression: &Expression, factor: &Expression, scope: &Scope) -> Option<Expression> {
+    let same = |e: &Expression| e == factor || (scope.species_power(e).is_some() &&
+        scope.species_power(e) == scope.species_power(factor));
+    if same(expression) {
+        Some(Expression::Number(1.0))
+    } else if let &Expression::Apply(ref operator, ref args) = expression {
+        if operator == "times" {
+            args.iter().position(|a| same(a)).map(|i| {
+                let mut rest = args.clone();
+                rest.remove(i);
+                Expression::Apply("times".to_string(), rest)
+            })
+        } else { None }
+    } else { None }
+}
+
@@ -0,0 +1,341 @@
+use std::collections::HashMap;
+
+use super::super::model::Model;
+use super::super::model::StateID;
+use super::super::model::StateCoordinate;
+use super::super::model::VariableIndex;
+
+/// A reduced ordered binary decision diagram over a fixed number of boolean variables.
+/// Nodes are stored in an arena that contains only nodes reachable from the root.
+/// Index 0 is the false terminal, index 1 is the true terminal.
+#[derive(Debug, Clone)]
+pub struct Bdd {
+    num_vars: usize,
+    nodes: Vec<Node>,
+    root: usize
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Node {
+    var: usize,
+    low: usize,
+    high: usize
+}
+
+//Creates the nodes of a new diagram bottom up, children always before their parents.
+//Every created node ends up reachable from the root, because each result of `mk` is used
+//as a child of the next one.
+struct Builder {
+    num_vars: usize,
+    nodes: Vec<Node>,
+    unique: HashMap<Node, usize>
+}
+
+impl Builder {
+    fn new(num_vars: usize) -> Builder {
+        Builder {
+            num_vars: num_vars,
+            nodes: vec![Node { var: num_vars, low: 0, high: 0 }, Node { var: num_vars, low: 1, high: 1 }],
+            unique: HashMap::new()
+        }
+    }
+
+    fn mk(&mut self, var: usize, low: usize, high: usize) -> usize {
+        if low == high {
+            return low;
+        }
+        let node = Node { var: var, low: low, high: high };
+        if let Some(index) = self.unique.get(&node) {
+            return *index;
+        }
+        self.nodes.push(node);
+        self.unique.insert(node, self.nodes.len() - 1);
+        self.nodes.len() - 1
+    }
+
+    fn finish(self, root: usize) -> Bdd {
+        Bdd { num_vars: self.num_vars, nodes: self.nodes, root: root }
+    }
+}
+
+impl Bdd {
+
+    pub fn mk_false(num_vars: usize) -> Bdd {
+        Builder::new(num_vars).finish(0)
+    }
+
+    pub fn mk_true(num_vars: usize) -> Bdd {
+        Builder::new(num_vars).finish(1)
+    }
+
+    /// The diagram of all assignments in which the variable has the given value.
+    pub fn mk_literal(num_vars: usize, var: usize, value: bool) -> Bdd {
+        let mut builder = Builder::new(num_vars);
+        let root = if value { builder.mk(var, 0, 1) } else { builder.mk(var, 1, 0) };
+        builder.finish(root)
+    }
+
+    pub fn num_vars(&self) -> usize {
+        self.num_vars
+    }
+
+    pub fn is_false(&self) -> bool {
+        self.root == 0
+    }
+
+    pub fn is_true(&self) -> bool {
+        self.root == 1
+    }
+
+    /// Number of decision nodes of the diagram (terminals are not counted).
+    pub fn size(&self) -> usize {
+        self.nodes.len() - 2
+    }
+
+    pub fn and(&self, other: &Bdd) -> Bdd {
+        self.apply(other, &|a, b| a && b)
+    }
+
+    pub fn or(&self, other: &Bdd) -> Bdd {
+        self.apply(other, &|a, b| a || b)
+    }
+
+    /// Assignments of this diagram that are not assignments of the other one.
+    pub fn minus(&self, other: &Bdd) -> Bdd {
+        self.apply(other, &|a, b| a && !b)
+    }
+
+    pub fn not(&self) -> Bdd {
+        //swapping the terminals keeps the diagram reduced
+        let swap = |i: usize| if i < 2 { 1 - i } else { i };
+        Bdd {
+            num_vars: self.num_vars,
+            nodes: self.nodes.iter().enumerate().map(|(i, node)| {
+                if i < 2 { *node } else { Node { var: node.var, low: swap(node.low), high: swap(node.high) } }
+            }).collect(),
+            root: swap(self.root)
+        }
+    }
+
+    /// Fix the variable to the given value. The result does not depend on the variable.
+    pub fn restrict(&self, var: usize, value: bool) -> Bdd {
+        let mut builder = Builder::new(self.num_vars);
+        let mut cache = HashMap::new();
+        let root = self.restrict_node(self.root, var, value, &mut cache, &mut builder);
+        builder.finish(root)
+    }
+
+    fn restrict_node(&self, node: usize, var: usize, value: bool,
+        cache: &mut HashMap<usize, usize>, builder: &mut Builder
+    ) -> usize {
+        if node < 2 {
+            return node;
+        }
+        if let Some(result) = cache.get(&node) {
+            return *result;
+        }
+        let n = self.nodes[node];
+        let result = if n.var == var {
+            self.restrict_node(if value { n.high } else { n.low }, var, value, cache, builder)
+        } else {
+            let low = self.restrict_node(n.low, var, value, cache, builder);
+            let high = self.restrict_node(n.high, var, value, cache, builder);
+            builder.mk(n.var, low, high)
+        };
+        cache.insert(node, result);
+        result
+    }
+
+    fn apply<F: Fn(bool, bool) -> bool>(&self, other: &Bdd, op: &F) -> Bdd {
+        assert_eq![self.num_vars, other.num_vars];
+        let mut builder = Builder::new(self.num_vars);
+        let mut cache = HashMap::new();
+        let root = self.apply_node(other, self.root, other.root, op, &mut cache, &mut builder);
+        builder.finish(root)
+    }
+
+    fn apply_node<F: Fn(bool, bool) -> bool>(
+        &self, other: &Bdd, left: usize, right: usize, op: &F,
+        cache: &mut HashMap<(usize, usize), usize>, builder: &mut Builder
+    ) -> usize {
+        if left < 2 && right < 2 {
+            return if op(left == 1, right == 1) { 1 } else { 0 };
+        }
+        if let Some(result) = cache.get(&(left, right)) {
+            return *result;
+        }
+        let (l, r) = (self.nodes[left], other.nodes[right]);
+        let var = l.var.min(r.var);
+        let (left_low, left_high) = if l.var == var { (l.low, l.high) } else { (left, left) };
+        let (right_low, right_high) = if r.var == var { (r.low, r.high) } else { (right, right) };
+        let low = self.apply_node(other, left_low, right_low, op, cache, builder);
+        let high = self.apply_node(other, left_high, right_high, op, cache, builder);
+        let result = builder.mk(var, low, high);
+        cache.insert((left, right), result);
+        result
+    }
+
+    /// Call the function for every satisfying assignment of the diagram.
+    pub fn for_each_assignment<F: FnMut(&Vec<bool>)>(&self, mut f: F) {
+        let mut assignment = vec![false; self.num_vars];
+        self.visit(self.root, 0, &mut assignment, &mut f);
+    }
+
+    fn visit<F: FnMut(&Vec<bool>)>(&self, node: usize, var: usize, assignment: &mut Vec<bool>, f: &mut F) {
+        if node == 0 {
+            return;
+        }
+        if var == self.num_vars {
+            f(assignment);
+            return;
+        }
+        let n = self.nodes[node];
+        let (low, high) = if n.var == var { (n.low, n.high) } else { (node, node) };
+        assignment[var] = false;
+        self.visit(low, var + 1, assignment, f);
+        assignment[var] = true;
+        self.visit(high, var + 1, assignment, f);
+    }
+}
+
+/// Binary encoding of rectangle coordinates. Every model variable is encoded by
+/// ceil(log2(number of rectangles)) bits, most significant bit first, variables
+/// ordered by their index.
+#[derive(Debug, Clone)]
+pub struct StateEncoding {
+    counts: Vec<usize>,
+    offsets: Vec<usize>,
+    bits: Vec<usize>,
+    num_vars: usize
+}
+
+impl StateEncoding {
+
+    pub fn new(model: &Model) -> StateEncoding {
+        let counts: Vec<usize> = model.variables.iter().map(|t| t.len() - 1).collect();
+        let bits: Vec<usize> = counts.iter().map(|count| {
+            let mut bits = 0;
+            while (1 << bits) < *count {
+                bits += 1;
+            }
+            bits
+        }).collect();
+        let mut offsets = vec![];
+        let mut num_vars = 0;
+        for b in &bits {
+            offsets.push(num_vars);
+            num_vars += *b;
+        }
+        StateEncoding { counts: counts, offsets: offsets, bits: bits, num_vars: num_vars }
+    }
+
+    pub fn num_vars(&self) -> usize {
+        self.num_vars
+    }
+
+    /// All valid states (encodings of coordinates beyond the number of rectangles are excluded).
+    pub fn universe(&self) -> Bdd {
+        (0..self.counts.len()).fold(Bdd::mk_true(self.num_vars), |acc, d| {
+            acc.and(&self.at_most(d, self.counts[d] - 1))
+        })
+    }
+
+    /// Valid states of which the coordinate of variable d lies in [from, to].
+    pub fn coordinate_range(&self, d: VariableIndex, from: StateCoordinate, to: StateCoordinate) -> Bdd {
+        if from > to {
+            Bdd::mk_false(self.num_vars)
+        } else {
+            self.universe().and(&self.at_least(d, from)).and(&self.at_most(d, to))
+        }
+    }
+
+    /// The diagram containing exactly one state.
+    pub fn state(&self, model: &Model, state: &StateID) -> Bdd {
+        let mut builder = Builder::new(self.num_vars);
+        let mut node = 1;
+        for d in (0..self.counts.len()).rev() {
+            let coordinate = model.extract_coordinate(state, &d);
+            for j in (0..self.bits[d]).rev() {
+                let var = self.offsets[d] + j;
+                node = if self.bit(d, coordinate, j) { builder.mk(var, 0, node) } else { builder.mk(var, node, 0) };
+            }
+        }
+        builder.finish(node)
+    }
+
+    /// The diagram containing the given states.
+    pub fn states(&self, model: &Model, states: &[StateID]) -> Bdd {
+        //balanced unions keep intermediate diagrams small
+        match states.len() {
+            0 => Bdd::mk_false(self.num_vars),
+            1 => self.state(model, &states[0]),
+            n => self.states(model, &states[..(n / 2)]).or(&self.states(model, &states[(n / 2)..]))
+        }
+    }
+
+    /// States of which the upper neighbour along variable d is in the diagram.
+    pub fn lower_neighbours(&self, bdd: &Bdd, d: VariableIndex) -> Bdd {
+        self.shift(bdd, d, true)
+    }
+
+    /// States of which the lower neighbour along variable d is in the diagram.
+    pub fn upper_neighbours(&self, bdd: &Bdd, d: VariableIndex) -> Bdd {
+        self.shift(bdd, d, false)
+    }
+
+    //States whose coordinate of d plus one (minus one without increment) is in the diagram.
+    //Adding one flips the k trailing ones and the zero above them, so for every k the flipped bits
+    //of the state are fixed by literals and the same bits of the neighbour are fixed in the diagram.
+    fn shift(&self, bdd: &Bdd, d: VariableIndex, increment: bool) -> Bdd {
+        let bits = self.bits[d];
+        let mut result = Bdd::mk_false(self.num_vars);
+        for k in 0..bits {
+            let top = bits - 1 - k;
+            let mut neighbour = bdd.clone();
+            let mut state = Bdd::mk_true(self.num_vars);
+            for j in top..bits {
+                let var = self.offsets[d] + j;
+                let value = if j == top { !increment } else { increment };
+                neighbour = neighbour.restrict(var, !value);
+                state = state.and(&Bdd::mk_literal(self.num_vars, var, value));
+            }
+            result = result.or(&neighbour.and(&state));
+        }
+        result.and(&self.universe())
+    }
+
+    /// Decode all states of the diagram.
+    pub fn decode(&self, model: &Model, bdd: &Bdd) -> Vec<StateID> {
+        let mut result = vec![];
+        bdd.for_each_assignment(|assignment| {
+            let state: Vec<StateCoordinate> = (0..self.counts.len()).map(|d| {
+                (0..self.bits[d]).fold(0, |acc, j| acc * 2 + if assignment[self.offsets[d] + j] { 1 } else { 0 })
+            }).collect();
+            result.push(model.encode_state(&state));
+        });
+        result
+    }
+
+    //j-th bit of the coordinate, 0 being the most significant one
+    fn bit(&self, d: VariableIndex, coordinate: StateCoordinate, j: usize) -> bool {
+        (coordinate >> (self.bits[d] - 1 - j)) & 1 == 1
+    }
+
+    //Coordinates of variable d that are at most k (a bit by bit comparison from the least significant bit).
+    fn at_most(&self, d: VariableIndex, k: StateCoordinate) -> Bdd {
+        let mut builder = Builder::new(self.num_vars);
+        let mut node = 1;
+        for j in (0..self.bits[d]).rev() {
+            let var = self.offsets[d] + j;
+            node = if self.bit(d, k, j) { builder.mk(var, 1, node) } else { builder.mk(var, node, 0) };
+        }
+        builder.finish(node)
+    }
+
+    fn at_least(&self, d: VariableIndex, k: StateCoordinate) -> Bdd {
+        match k.checked_sub(1) {
+            Some(below) => self.at_most(d, below).not(),
+            None => Bdd::mk_true(self.num_vars)
+        }
+    }
+}
@@ -0,0 +1,428 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
+
+use super::checker::check;
+use super::checker::CheckerContext;
+use super::checker::Fairness;
+use super::formula::Formula;
+use super::formula::Formula::*;
+use super::formula::Proposition;
+use super::generator::StateSet2;
+use super::generator::initial_states;
+use super::generator::coordinate_range;
+use super::generator::compute_directed_edges;
+use super::model::Model;
+use super::model::StateID;
+use super::parameters::Colors;
+
+use self::bdd::Bdd;
+use self::bdd::StateEncoding;
+
+pub mod bdd;
+
+/// Representation of a set of states together with their colors, used by `check_with`.
+/// Results of all operations contain the same colors as the corresponding parts of `checker::check`.
+pub trait StateSet<C: Colors> : Sized + Clone {
+
+    /// States satisfying the proposition.
+    fn from_proposition(model: &Model, proposition: &Proposition) -> Self;
+
+    fn from_explicit(model: &Model, set: &StateSet2<C>) -> Self;
+
+    fn to_explicit(&self, model: &Model) -> StateSet2<C>;
+
+    fn is_empty(&self) -> bool;
+
+    /// Intersection of states and colors (restricted to the model bounds).
+    fn and(&self, other: &Self, model: &Model) -> Self;
+
+    /// Union of states and colors.
+    fn or(&self, other: &Self) -> Self;
+
+    /// Colors of this set that are not in the other set.
+    fn minus(&self, other: &Self) -> Self;
+
+    /// Complement with respect to all states (restricted to the model bounds).
+    fn not(&self, model: &Model) -> Self;
+
+    /// States and colors that have a successor in this set (restricted to the model bounds).
+    fn pre(&self, ctx: &mut CheckerContext<C>) -> Self;
+}
+
+impl<C: Colors + Debug + Clone> StateSet<C> for StateSet2<C> {
+
+    fn from_proposition(model: &Model, proposition: &Proposition) -> StateSet2<C> {
+        initial_states::<C>(model, proposition)
+    }
+
+    fn from_explicit(_: &Model, set: &StateSet2<C>) -> StateSet2<C> {
+        set.clone()
+    }
+
+    fn to_explicit(&self, _: &Model) -> StateSet2<C> {
+        self.clone()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.values().all(|colors| colors.is_empty())
+    }
+
+    fn and(&self, other: &StateSet2<C>, model: &Model) -> StateSet2<C> {
+        let bounds = C::model_bounds(model);
+        let mut result = HashMap::new();
+        for (state, colors) in self {
+            if let Some(c) = other.get(state) {
+                let colors = colors.and(c).and(&bounds);
+                if !colors.is_empty() {
+                    result.insert(*state, colors);
+                }
+            }
+        }
+        result
+    }
+
+    fn or(&self, other: &StateSet2<C>) -> StateSet2<C> {
+        let mut result = self.clone();
+        for (state, colors) in other {
+            let colors = match result.get(state) {
+                Some(c) => c.or(colors),
+                None => colors.clone()
+            };
+            result.insert(*state, colors);
+        }
+        result
+    }
+
+    fn minus(&self, other: &StateSet2<C>) -> StateSet2<C> {
+        let mut result = HashMap::new();
+        for (state, colors) in self {
+            let colors = match other.get(state) {
+                Some(c) => colors.and(&c.not()),
+                None => colors.clone()
+            };
+            if !colors.is_empty() {
+                result.insert(*state, colors);
+            }
+        }
+        result
+    }
+
+    fn not(&self, model: &Model) -> StateSet2<C> {
+        let bounds = C::model_bounds(model);
+        let mut result = HashMap::new();
+        for (state, colors) in initial_states::<C>(model, &Proposition::True) {
+            let colors = match self.get(&state) {
+                Some(c) => colors.and(&c.not()).and(&bounds),
+                None => colors
+            };
+            if !colors.is_empty() {
+                result.insert(state, colors);
+            }
+        }
+        result
+    }
+
+    fn pre(&self, ctx: &mut CheckerContext<C>) -> StateSet2<C> {
+        let bounds = C::model_bounds(&ctx.model);
+        let mut result: StateSet2<C> = HashMap::new();
+        for (state, colors) in self {
+            for (predecessor, edge_colors) in compute_directed_edges::<C>(ctx, state, &false) {
+                let pushed = edge_colors.and(colors).and(&bounds);
+                if !pushed.is_empty() {
+                    let pushed = match result.get(&predecessor) {
+                        Some(c) => c.or(&pushed),
+                        None => pushed
+                    };
+                    result.insert(predecessor, pushed);
+                }
+            }
+        }
+        result
+    }
+}
+
+/// Symbolic representation of a state set. States are grouped by their colors and
+/// every group is stored as a decision diagram over the bits of threshold indices
+/// (see `StateEncoding`), so large regions with uniform colors are cheap to represent.
+/// Groups are disjoint and have distinct, non-empty colors.
+#[derive(Debug, Clone)]
+pub struct SymbolicSet<C: Colors> {
+    pub groups: Vec<(Bdd, C)>
+}
+
+impl<C: Colors + Debug + Clone> SymbolicSet<C> {
+
+    pub fn empty() -> SymbolicSet<C> {
+        SymbolicSet { groups: vec![] }
+    }
+
+    /// Union of all groups.
+    pub fn states(&self, model: &Model) -> Bdd {
+        self.covered().unwrap_or_else(|| Bdd::mk_false(StateEncoding::new(model).num_vars()))
+    }
+
+    fn covered(&self) -> Option<Bdd> {
+        self.groups.iter().fold(None, |acc, &(ref states, _)| match acc {
+            Some(acc) => Some(states.or(&acc)),
+            None => Some(states.clone())
+        })
+    }
+
+    //Groups restricted to the given states, with colors intersected with the given colors.
+    fn restrict(&self, states: &Bdd, colors: &C) -> SymbolicSet<C> {
+        let mut result = SymbolicSet::empty();
+        for &(ref group, ref group_colors) in &self.groups {
+            result.insert(group.and(states), group_colors.and(colors));
+        }
+        result
+    }
+
+    //Add states that are not in any group yet.
+    fn insert(&mut self, states: Bdd, colors: C) {
+        if states.is_false() || colors.is_empty() {
+            return;
+        }
+        for group in &mut self.groups {
+            if same_colors(&group.1, &colors) {
+                group.0 = group.0.or(&states);
+                return;
+            }
+        }
+        self.groups.push((states, colors));
+    }
+}
+
+//Equality of colors can be a one-way test (for Order1 it is inclusion), so groups
+//are only merged when their colors are equal in both directions.
+fn same_colors<C: Colors>(a: &C, b: &C) -> bool {
+    a == b && b == a
+}
+
+impl<C: Colors + Debug + Clone> StateSet<C> for SymbolicSet<C> {
+
+    fn from_proposition(model: &Model, proposition: &Proposition) -> SymbolicSet<C> {
+        let encoding = StateEncoding::new(model);
+        let mut result = SymbolicSet::empty();
+        match proposition {
+            &Proposition::False => {},
+            &Proposition::True | &Proposition::Equation(..) | &Proposition::Value(..) | &Proposition::Range(..) => {
+                if let Some((d, from, to)) = coordinate_range(model, proposition) {
+                    result.insert(encoding.coordinate_range(d, from, to), C::tt());
+                }
+            }
+            &Proposition::Parameters(ref coefficients, ref op, value) => {
+                //unsupported constraints are satisfied by no state, as in `initial_states`
+                if let Some(colors) = C::parameter_constraint(model, coefficients, op, value) {
+                    result.insert(encoding.universe(), colors);
+                }
+            }
+            &Proposition::Linear(..) => return SymbolicSet::from_explicit(model, &initial_states::<C>(model, proposition))
+        }
+        result
+    }
+
+    fn from_explicit(model: &Model, set: &StateSet2<C>) -> SymbolicSet<C> {
+        let encoding = StateEncoding::new(model);
+        let mut groups: Vec<(Vec<StateID>, C)> = vec![];
+        for (state, colors) in set {
+            if colors.is_empty() {
+                continue;
+            }
+            match groups.iter().position(|&(_, ref c)| same_colors(c, colors)) {
+                Some(i) => groups[i].0.push(*state),
+                None => groups.push((vec![*state], colors.clone()))
+            }
+        }
+        SymbolicSet {
+            groups: groups.into_iter().map(|(mut states, colors)| {
+                states.sort();
+                (encoding.states(model, &states), colors)
+            }).collect()
+        }
+    }
+
+    fn to_explicit(&self, model: &Model) -> StateSet2<C> {
+        let encoding = StateEncoding::new(model);
+        let mut result = HashMap::new();
+        for &(ref states, ref colors) in &self.groups {
+            for state in encoding.decode(model, states) {
+                result.insert(state, colors.clone());
+            }
+        }
+        result
+    }
+
+    fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+
+    fn and(&self, other: &SymbolicSet<C>, model: &Model) -> SymbolicSet<C> {
+        let bounds = C::model_bounds(model);
+        let mut result = SymbolicSet::empty();
+        for &(ref left, ref left_colors) in &self.groups {
+            for &(ref right, ref right_colors) in &other.groups {
+                result.insert(left.and(right), left_colors.and(right_colors).and(&bounds));
+            }
+        }
+        result
+    }
+
+    fn or(&self, other: &SymbolicSet<C>) -> SymbolicSet<C> {
+        let mut result = SymbolicSet::empty();
+        for &(ref left, ref left_colors) in &self.groups {
+            for &(ref right, ref right_colors) in &other.groups {
+                result.insert(left.and(right), left_colors.or(right_colors));
+            }
+        }
+        let left_states = self.covered();
+        let right_states = other.covered();
+        for &(ref left, ref colors) in &self.groups {
+            let only = match right_states { Some(ref r) => left.minus(r), None => left.clone() };
+            result.insert(only, colors.clone());
+        }
+        for &(ref right, ref colors) in &other.groups {
+            let only = match left_states { Some(ref l) => right.minus(l), None => right.clone() };
+            result.insert(only, colors.clone());
+        }
+        result
+    }
+
+    fn minus(&self, other: &SymbolicSet<C>) -> SymbolicSet<C> {
+        let mut result = SymbolicSet::empty();
+        let other_states = other.covered();
+        for &(ref left, ref left_colors) in &self.groups {
+            for &(ref right, ref right_colors) in &other.groups {
+                result.insert(left.and(right), left_colors.and(&right_colors.not()));
+            }
+            let only = match other_states { Some(ref r) => left.minus(r), None => left.clone() };
+            result.insert(only, left_colors.clone());
+        }
+        result
+    }
+
+    fn not(&self, model: &Model) -> SymbolicSet<C> {
+        let bounds = C::model_bounds(model);
+        let mut result = SymbolicSet::empty();
+        for &(ref states, ref colors) in &self.groups {
+            result.insert(states.clone(), colors.not().and(&bounds));
+        }
+        //states outside of the set keep all colors, as in `checker::check`
+        let outside = StateEncoding::new(model).universe().minus(&self.states(model));
+        result.insert(outside, C::tt());
+        result
+    }
+
+    fn pre(&self, ctx: &mut CheckerContext<C>) -> SymbolicSet<C> {
+        if ctx.symbolic_edges.is_none() {
+            let transitions = Transitions::new(ctx);
+            ctx.symbolic_edges = Some(transitions);
+        }
+        let model = &ctx.model;
+        let transitions = ctx.symbolic_edges.as_ref().unwrap();
+        let encoding = StateEncoding::new(model);
+        let bounds = C::model_bounds(model);
+        let mut result = SymbolicSet::empty();
+        for &(ref states, ref colors) in &self.groups {
+            let colors = colors.and(&bounds);
+            for d in 0..model.variables.len() {
+                result = result.or(&transitions.up[d].restrict(&encoding.lower_neighbours(states, d), &colors));
+                result = result.or(&transitions.down[d].restrict(&encoding.upper_neighbours(states, d), &colors));
+            }
+            result = result.or(&transitions.self_loops.restrict(states, &colors));
+        }
+        result
+    }
+}
+
+/// Transition relation in the symbolic representation. For every variable, the states with an edge
+/// to the upper (lower) neighbour are grouped by the colors of the edge, self-loops are grouped
+/// the same way. The colors of edges are computed from the vertices of every state, so the relation
+/// is built once from the explicit edges and then shared by all `pre` computations of a context.
+pub struct Transitions<C: Colors> {
+    up: Vec<SymbolicSet<C>>,
+    down: Vec<SymbolicSet<C>>,
+    self_loops: SymbolicSet<C>
+}
+
+impl<C: Colors + Debug + Clone> Transitions<C> {
+
+    pub fn new(ctx: &mut CheckerContext<C>) -> Transitions<C> {
+        let dimensions = ctx.model.variables.len();
+        let mut up: Vec<StateSet2<C>> = (0..dimensions).map(|_| HashMap::new()).collect();
+        let mut down: Vec<StateSet2<C>> = (0..dimensions).map(|_| HashMap::new()).collect();
+        let mut self_loops = HashMap::new();
+        for (state, _) in initial_states::<C>(&ctx.model, &Proposition::True) {
+            for (successor, colors) in compute_directed_edges::<C>(ctx, &state, &true) {
+                let ref model = ctx.model;
+                if successor == state {
+                    self_loops.insert(state, colors);
+                } else if let Some(d) = (0..dimensions).find(|d| model.upper_neighbour(&state, d) == Some(successor)) {
+                    up[d].insert(state, colors);
+                } else if let Some(d) = (0..dimensions).find(|d| model.lower_neighbour(&state, d) == Some(successor)) {
+                    down[d].insert(state, colors);
+                }
+            }
+        }
+        let model = &ctx.model;
+        Transitions {
+            up: up.iter().map(|set| SymbolicSet::from_explicit(model, set)).collect(),
+            down: down.iter().map(|set| SymbolicSet::from_explicit(model, set)).collect(),
+            self_loops: SymbolicSet::from_explicit(model, &self_loops)
+        }
+    }
+}
+
+/// Check the formula using the given state set representation.
+/// Boolean operators, EX, EU, EF, AX, AG and (without fairness) EG are evaluated on the
+/// representation directly, other operators are computed by `checker::check` and converted.
+pub fn check_with<C, S>(ctx: &mut CheckerContext<C>, formula: &Formula) -> S
+    where C: Colors + Debug + Clone, S: StateSet<C>
+{
+    let tt = || Box::new(Atom(Proposition::True));
+    match formula {
+        &Atom(ref prop) => S::from_proposition(&ctx.model, prop),
+        &Not(ref inner) => check_with::<C, S>(ctx, inner).not(&ctx.model),
+        &And(ref left, ref right) => {
+            let left = check_with::<C, S>(ctx, left);
+            let right = check_with::<C, S>(ctx, right);
+            left.and(&right, &ctx.model)
+        }
+        &Or(ref left, ref right) => {
+            let left = check_with::<C, S>(ctx, left);
+            let right = check_with::<C, S>(ctx, right);
+            left.or(&right)
+        }
+        &Implies(ref left, ref right) => check_with::<C, S>(ctx, &Or(Box::new(Not(left.clone())), right.clone())),
+        &EX(ref inner) => check_with::<C, S>(ctx, inner).pre(ctx),
+        &AX(ref inner) => check_with::<C, S>(ctx, &Not(Box::new(EX(Box::new(Not(inner.clone())))))),
+        &EF(ref inner) => check_with::<C, S>(ctx, &EU(tt(), inner.clone())),
+        &AG(ref inner) => check_with::<C, S>(ctx, &Not(Box::new(EF(Box::new(Not(inner.clone())))))),
+        &EU(ref path, ref reach) => {
+            let path = check_with::<C, S>(ctx, path);
+            let mut result = check_with::<C, S>(ctx, reach);
+            let mut frontier = result.clone();
+            while !frontier.is_empty() {
+                let new = frontier.pre(ctx).and(&path, &ctx.model).minus(&result);
+                result = result.or(&new);
+                frontier = new;
+            }
+            result
+        }
+        &EG(ref inner) if ctx.fairness == Fairness::Disabled => {
+            //intersection with all states restricts the colors to the model bounds
+            let all = S::from_proposition(&ctx.model, &Proposition::True);
+            let mut result = check_with::<C, S>(ctx, inner).and(&all, &ctx.model);
+            loop {
+                let next = result.and(&result.pre(ctx), &ctx.model);
+                if result.minus(&next).is_empty() {
+                    return result;
+                }
+                result = next;
+            }
+        }
+        _ => {
+            let explicit = check::<C>(ctx, formula);
+            S::from_explicit(&ctx.model, &explicit)
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use rustc_serialize::json::{ToJson, Json};
+use json_utils::{JsonMap, create_object};
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Severity {
+    Error, Warning
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum IssueKind {
+    NoVariables,
+    EmptyRange,
+    NoThresholds,
+    SingleThreshold,
+    EmptyEquation,
+    InvalidVariableIndex,
+    InvalidParameterIndex,
+    EmptyRampApproximation,
+    NotFinite,
+    UnsortedThresholds,
+    DuplicateThreshold,
+    ThresholdOutOfRange,
+    MissingBoundaryThreshold,
+    UnsortedApproximation,
+    EmptyRamp,
+    NegativeHillCoefficient,
+    NonPositiveHillThreshold
+}
+
+/// One problem found in the model. Path is a JSON path to the offending element,
+/// for example `variables[1].equation[2].evaluables[0]`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Issue {
+    pub kind: IssueKind,
+    pub severity: Severity,
+    pub path: String,
+    pub message: String
+}
+
+/// A list of all problems found during model validation, in the order of appearance.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ValidationReport {
+    pub issues: Vec<Issue>
+}
+
+impl ValidationReport {
+
+    pub fn new() -> ValidationReport {
+        ValidationReport { issues: vec![] }
+    }
+
+    pub fn error(&mut self, kind: IssueKind, path: String, message: String) {
+        self.issues.push(Issue { kind: kind, severity: Severity::Error, path: path, message: message });
+    }
+
+    pub fn warning(&mut self, kind: IssueKind, path: String, message: String) {
+        self.issues.push(Issue { kind: kind, severity: Severity::Warning, path: path, message: message });
+    }
+
+    /// True if the report contains no errors (warnings are allowed).
+    pub fn is_valid(&self) -> bool {
+        self.errors().is_empty()
+    }
+
+    pub fn errors(&self) -> Vec<&Issue> {
+        self.issues.iter().filter(|i| i.severity == Severity::Error).collect()
+    }
+
+    pub fn warnings(&self) -> Vec<&Issue> {
+        self.issues.iter().filter(|i| i.severity == Severity::Warning).collect()
+    }
+}
+
+impl ToJson for Severity {
+    fn to_json(&self) -> Json {
+        Json::String(match self {
+            &Severity::Error => "error",
+            &Severity::Warning => "warning"
+        }.to_string())
+    }
+}
+
+impl ToJson for IssueKind {
+    fn to_json(&self) -> Json {
+        Json::String(format!("{:?}", self))
+    }
+}
+
+impl ToJson for Issue {
+    fn to_json(&self) -> Json {
+        create_object(|map| {
+            map.write_item("kind", &self.kind);
+            map.write_item("severity", &self.severity);
+            map.write_item("path", &self.path);
+            map.write_item("message", &self.message);
+        })
+    }
+}
+
+impl ToJson for ValidationReport {
+    fn to_json(&self) -> Json {
+        self.issues.to_json()
+    }
+}
@@ -0,0 +1,73 @@
+use ode_model::analysis::{attractors, export_attractors, Attractor, components};
+use ode_model::checker::CheckerContext;
+use ode_model::generator::compute_directed_edges;
+use ode_model::parameters::Colors;
+use ode_model::parameters::order_0::Order0;
+use ode_model::parameters::order_1::Order1;
+
+//For the colors of the attractor, no transition leaves it.
+fn assert_terminal<C: Colors + ::std::fmt::Debug + Clone>(ctx: &mut CheckerContext<C>, attractor: &Attractor<C>) {
+    for (state, colors) in &attractor.states {
+        for (successor, edge_colors) in compute_directed_edges::<C>(ctx, state, &true) {
+            let leaving = edge_colors.and(colors);
+            let inside = attractor.states.get(&successor).cloned().unwrap_or(C::ff());
+            assert!(leaving.and(&inside.not()).is_empty());
+        }
+    }
+}
+
+#[test]
+fn single_stable_state() {
+    let model = super::stable_model().compile().unwrap();
+    let equilibrium = model.encode_state(&vec![1, 1]);
+    let mut ctx = CheckerContext::new(model).unwrap();
+    let result = attractors::<Order0>(&mut ctx);
+    assert_eq!(1, result.len());
+    assert!(result[0].states.contains_key(&equilibrium));
+    assert_terminal(&mut ctx, &result[0]);
+}
+
+#[test]
+fn parametrised_attractors() {
+    let full_model = super::parametrised_model();
+    let model = full_model.compile().unwrap();
+    let mut ctx = CheckerContext::new(model.clone()).unwrap();
+    let result = attractors::<Order1>(&mut ctx);
+    //every parametrisation of a finite graph has at least one attractor
+    let covered = result.iter().fold(Order1::ff(), |acc, a| acc.or(&a.colors));
+    assert_eq!(Order1::model_bounds(&model), covered);
+    for attractor in &result {
+        assert!(attractor.colors.is_not_empty());
+        assert_terminal(&mut ctx, attractor);
+    }
+    let json = export_attractors(&model, &full_model, &result);
+    assert_eq!(result.len(), json.find("attractors").unwrap().as_array().unwrap().len());
+}
+
+#[test]
+fn components_partition_states_for_every_color() {
+    let model = super::parametrised_model().compile().unwrap();
+    let bounds = Order1::model_bounds(&model);
+    let mut ctx = CheckerContext::new(model.clone()).unwrap();
+    let result = components::<Order1>(&mut ctx);
+    for state in 0..16 {
+        let mut covered = Order1::ff();
+        for component in &result {
+            if let Some(colors) = component.states.get(&state) {
+                assert!(covered.and(colors).is_empty());
+                covered = covered.or(colors);
+            }
+        }
+        assert_eq!(bounds, covered);
+    }
+}
+
+#[test]
+fn stable_state_is_cyclic() {
+    let model = super::stable_model().compile().unwrap();
+    let equilibrium = model.encode_state(&vec![1, 1]);
+    let mut ctx = CheckerContext::new(model).unwrap();
+    let result = components::<Order0>(&mut ctx);
+    let component = result.iter().find(|c| c.states.contains_key(&equilibrium)).unwrap();
+    assert!(component.cyclic.is_not_empty());
+}
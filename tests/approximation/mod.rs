@@ -0,0 +1,94 @@
+extern crate ode_model;
+
+use ode_model::*;
+use ode_model::approximation::hill;
+use ode_model::approximation::sigmoid;
+use ode_model::approximation::sample;
+use ode_model::model::evaluable::Evaluable2;
+use ode_model::model::CompileError;
+use ode_model::validation::IssueKind;
+
+fn single_variable_model(var_points: Option<VarPoints>, evaluable: Evaluable) -> OdeModel {
+    OdeModel { name: "Test".to_string(), parameters: vec!(), variables: vec!(
+        Variable { name: "var1".to_string(), range: Range { min: 0.0, max: 10.0 },
+            thresholds: vec!(0.0, 2.5, 5.0, 10.0), var_points: var_points,
+            equation: vec!(Summand { constant: 1.0, variable_indices: vec!(),
+                parameter_indices: vec!(), evaluables: vec!(evaluable)
+            })
+        }
+    )}
+}
+
+#[test]
+fn hill_compiled_in_thresholds() {
+    let model = single_variable_model(None,
+        Evaluable::Hill { variable_index: 0, theta: 5.0, n: 2.0, a: 1.0, b: 3.0 }
+    ).compile().unwrap();
+    let Evaluable2(variable, ref function) = model.equations[0][0].functions[0];
+    assert_eq!(0, variable);
+    for t in &model.variables[0] {
+        assert_eq!(hill(*t, 5.0, 2.0, 1.0, 3.0), function.eval(t));
+    }
+}
+
+#[test]
+fn hill_with_zero_threshold_is_rejected() {
+    let model = single_variable_model(Some(VarPoints { point_count: 10, segment_count: 3 }),
+        Evaluable::Hill { variable_index: 0, theta: 0.0, n: 2.0, a: 1.0, b: 3.0 }
+    );
+    match model.compile() {
+        Err(CompileError::InvalidModel(report)) => {
+            assert_eq!(vec!(IssueKind::NonPositiveHillThreshold),
+                report.errors().iter().map(|i| i.kind.clone()).collect::<Vec<_>>());
+        }
+        _ => panic!["Hill function with zero threshold compiled"]
+    }
+}
+
+#[test]
+fn sigmoid_compiled_with_var_points() {
+    let model = single_variable_model(Some(VarPoints { point_count: 100, segment_count: 4 }),
+        Evaluable::Sigmoid { variable_index: 0, theta: 5.0, k: 2.0, a: 3.0, b: 1.0 }
+    );
+    let breakpoints = sample(&model.variables[0], |x| sigmoid(x, 5.0, 2.0, 3.0, 1.0));
+    assert_eq!(5, breakpoints.len());
+    let compiled = model.compile().unwrap();
+    let Evaluable2(_, ref function) = compiled.equations[0][0].functions[0];
+    //exact in the selected breakpoints
+    for p in &breakpoints {
+        assert!((sigmoid(p.threshold, 5.0, 2.0, 3.0, 1.0) - function.eval(&p.threshold)).abs() < 1e-12);
+    }
+    //four segments keep the error of the steep part below an eighth of the amplitude
+    for i in 0..1001 {
+        let x = i as f64 / 100.0;
+        assert!((sigmoid(x, 5.0, 2.0, 3.0, 1.0) - function.eval(&x)).abs() < 0.25);
+    }
+}
+
+#[test]
+fn optimal_segments_find_kink() {
+    let points: Vec<Point> = (0..11).map(|i| {
+        let x = i as f64;
+        Point { threshold: x, value: x.min(5.0) }
+    }).collect();
+    let segments = ode_model::approximation::optimal_segments(&points, 2);
+    assert_eq!(vec!(0.0, 5.0, 10.0), segments.iter().map(|p| p.threshold).collect::<Vec<f64>>());
+}
+
+#[test]
+fn approximate_adds_thresholds() {
+    let model = single_variable_model(Some(VarPoints { point_count: 101, segment_count: 3 }),
+        Evaluable::Hill { variable_index: 0, theta: 5.0, n: 4.0, a: 0.0, b: 1.0 }
+    );
+    let approximated = model.approximate();
+    assert!(approximated.is_multi_affine());
+    if let Evaluable::RampApproximation { ref approximation, .. }
+        = approximated.variables[0].equation[0].evaluables[0] {
+        assert_eq!(4, approximation.len());
+        for p in approximation {
+            assert!(approximated.variables[0].thresholds.contains(&p.threshold));
+        }
+    } else {
+        panic!["Hill was not approximated"];
+    }
+}
@@ -0,0 +1,66 @@
+extern crate ode_model;
+
+use ode_model::*;
+use ode_model::bio::*;
+use ode_model::parser::ParseError;
+
+static BIO_MODEL: &'static str = "# Sample model
+VARS: x, y
+PARAMS: k1, 0, 2
+CONSTS: c, 0.5; d, 2
+THRES: x: 0, 1, 5
+THRES: y: 0, 2 # comment
+VAR_POINTS: x: 1500, 10
+EQ: x = k1*Hillp(y, 2, 3, 0, 1) - c*x
+EQ: y = d*step(x, 1, 0, 1) - y
+";
+
+#[test]
+fn read_bio_model() {
+    let model = read_bio("Sample", BIO_MODEL).unwrap();
+    assert_eq!(vec!("x".to_string(), "y".to_string()),
+        model.variables.iter().map(|v| v.name.clone()).collect::<Vec<String>>());
+    assert_eq!(Parameter { name: "k1".to_string(), range: Range { min: 0.0, max: 2.0 } }, model.parameters[0]);
+    assert_eq!(Range { min: 0.0, max: 5.0 }, model.variables[0].range);
+    assert_eq!(Some(VarPoints { point_count: 1500, segment_count: 10 }), model.variables[0].var_points);
+    assert_eq!(vec!(
+        Summand { constant: 1.0, variable_indices: vec!(), parameter_indices: vec!(0), evaluables: vec!(
+            Evaluable::Hill { variable_index: 1, theta: 2.0, n: 3.0, a: 0.0, b: 1.0 }
+        )},
+        Summand { constant: -0.5, variable_indices: vec!(0), parameter_indices: vec!(), evaluables: vec!() }
+    ), model.variables[0].equation);
+    assert_eq!(2.0, model.variables[1].equation[0].constant);
+    assert!(model.is_valid().is_none());
+}
+
+#[test]
+fn write_bio_round_trip() {
+    let model = read_bio("Sample", BIO_MODEL).unwrap();
+    assert_eq!(model, read_bio("Sample", &write_bio(&model)).unwrap());
+}
+
+#[test]
+fn bio_error_position() {
+    let error = read_bio("Error", "VARS: x\nTHRES: x: 0, 1\nEQ: x = 2 * z\n").unwrap_err();
+    assert_eq!(ParseError { line: 3, column: 13, message: "Unknown identifier: z".to_string() }, error);
+}
+
+#[test]
+fn bio_negative_var_points() {
+    let error = read_bio("Error", "VARS: x\nTHRES: x: 0, 1\nVAR_POINTS: x: -10, 2\nEQ: x = 1\n").unwrap_err();
+    assert_eq!(ParseError { line: 3, column: 16, message: "Invalid count: -10".to_string() }, error);
+}
+
+#[test]
+fn bio_missing_sections_at_declaration() {
+    let error = read_bio("Error", "VARS: x, y\nTHRES: x: 0, 1\nTHRES: y: 0, 1\nEQ: x = 1\n").unwrap_err();
+    assert_eq!(ParseError { line: 1, column: 10, message: "Missing equation of variable y".to_string() }, error);
+    let error = read_bio("Error", "\nVARS: x\nEQ: x = 1\n").unwrap_err();
+    assert_eq!(ParseError { line: 2, column: 7, message: "Missing thresholds of variable x".to_string() }, error);
+}
+
+#[test]
+fn bio_duplicate_thresholds() {
+    let error = read_bio("Error", "VARS: x\nTHRES: x: 0, 1\nTHRES: x: 0, 2\nEQ: x = 1\n").unwrap_err();
+    assert_eq!(ParseError { line: 3, column: 7, message: "Duplicate thresholds of variable x".to_string() }, error);
+}
@@ -0,0 +1,412 @@
+extern crate ode_model;
+extern crate rustc_serialize;
+extern crate json_utils;
+
+use ode_model::bio::read_bio;
+use ode_model::formula::Formula;
+use ode_model::formula::Formula::*;
+use ode_model::formula::Proposition::*;
+use ode_model::formula::CompareOp::*;
+use ode_model::formula::VertexSemantics;
+use ode_model::formula::parser::parse_formula;
+use ode_model::generator::StateSet2;
+use ode_model::checker::check;
+use ode_model::checker::{validate, CheckError};
+use ode_model::checker::distances;
+use ode_model::checker::CheckerContext;
+use ode_model::checker::Fairness;
+use ode_model::checker::witness;
+use ode_model::checker::parallel::{ParallelContext, check_parallel};
+use ode_model::checker::distributed::{coordinate, run_worker};
+use ode_model::generator::compute_directed_edges;
+use ode_model::model::StateID;
+use ode_model::parameters::Colors;
+use ode_model::parameters::order_0::Order0;
+use ode_model::parameters::order_1::{Order1, Clause};
+use ode_model::types::Interval;
+use std::f64::NEG_INFINITY;
+use std::env;
+use std::fs;
+use std::fs::File;
+use std::io::Write;
+use std::process;
+use std::process::Command;
+use std::thread;
+use std::os::unix::net::UnixListener;
+use rustc_serialize::json::Json;
+use json_utils::{FromJson, JsonMap, create_object};
+
+fn check_text(text: &str) -> StateSet2<Order0> {
+    check_fair(text, Fairness::Disabled)
+}
+
+fn check_fair(text: &str, fairness: Fairness) -> StateSet2<Order0> {
+    let model = super::stable_model();
+    let formula = parse_formula(text, &model).unwrap();
+    let mut ctx = CheckerContext::new(model.compile().unwrap()).unwrap();
+    ctx.fairness = fairness;
+    check::<Order0>(&mut ctx, &formula)
+}
+
+fn check_formula(formula: &Formula) -> StateSet2<Order0> {
+    let model = super::stable_model();
+    let mut ctx = CheckerContext::new(model.compile().unwrap()).unwrap();
+    check::<Order0>(&mut ctx, formula)
+}
+
+fn not(formula: Formula) -> Formula {
+    Not(Box::new(formula))
+}
+
+#[test]
+fn or_matches_desugared() {
+    let p = parse_formula("x < 0.5", &super::stable_model()).unwrap();
+    let q = parse_formula("y > 1.5", &super::stable_model()).unwrap();
+    let desugared = not(And(Box::new(not(p.clone())), Box::new(not(q.clone()))));
+    assert_eq!(check_formula(&desugared), check_text("x < 0.5 || y > 1.5"));
+    let desugared = not(And(Box::new(p), Box::new(not(q))));
+    assert_eq!(check_formula(&desugared), check_text("x < 0.5 => y > 1.5"));
+}
+
+#[test]
+fn derived_temporal_operators_match_desugared() {
+    assert_eq!(check_text("!EX !(x < 0.5)"), check_text("AX x < 0.5"));
+    assert_eq!(check_text("E [ True U x > 1.5 ]"), check_text("EF x > 1.5"));
+    assert_eq!(check_text("A [ True U y < 1.5 ]"), check_text("AF y < 1.5"));
+    assert_eq!(check_text("!A [ True U !(x < 1.5) ]"), check_text("EG x < 1.5"));
+    assert_eq!(check_text("!E [ True U !(y > 0.5) ]"), check_text("AG y > 0.5"));
+}
+
+#[test]
+fn derived_operators_on_constants() {
+    assert_eq!(9, check_text("AG True").len());
+    assert_eq!(9, check_text("AF True").len());
+    assert_eq!(9, check_text("False => x < 0.5").len());
+    assert_eq!(0, check_text("EF False").len());
+    assert_eq!(0, check_text("AX False && EX True").len());
+}
+
+#[test]
+fn native_eg_matches_desugared() {
+    for p in &["True", "x < 0.5", "x > 0.5 && y < 1.5", "x > 1.5 || y > 1.5", "EF y > 1.5"] {
+        assert_eq!(check_text(&format!("!AF !({})", p)), check_text(&format!("EG ({})", p)));
+        assert_eq!(check_text(&format!("!EF !({})", p)), check_text(&format!("AG ({})", p)));
+    }
+}
+
+#[test]
+fn fairness_restricts_infinite_paths() {
+    for p in &["True", "x < 1.5", "y > 0.5 && x > 0.5", "!(x > 0.5 && x < 1.5 && y > 0.5 && y < 1.5)"] {
+        let fair = check_fair(&format!("EG ({})", p), Fairness::NoTransientLoops);
+        let unfair = check_text(&format!("EG ({})", p));
+        assert!(fair.keys().all(|s| unfair.contains_key(s)));
+        let fair = check_fair(&format!("AF !({})", p), Fairness::NoTransientLoops);
+        let unfair = check_text(&format!("AF !({})", p));
+        assert!(unfair.keys().all(|s| fair.contains_key(s)));
+    }
+    //finite path operators are not affected
+    assert_eq!(check_text("EF x > 1.5"), check_fair("EF x > 1.5", Fairness::NoTransientLoops));
+}
+
+#[test]
+fn fairness_removes_transient_self_loop() {
+    //the middle rectangle is a repeller: flow leaves through both facets, so its self-loop is transient
+    let model = read_bio("test", "VARS: x\nTHRES: x: 0, 0.5, 1.5, 2\nEQ: x = x - 1\n").unwrap();
+    let formula = parse_formula("EG (x > 0.5 && x < 1.5)", &model).unwrap();
+    let mut ctx = CheckerContext::new(model.compile().unwrap()).unwrap();
+    let unfair = check::<Order0>(&mut ctx, &formula);
+    ctx.fairness = Fairness::NoTransientLoops;
+    let fair = check::<Order0>(&mut ctx, &formula);
+    assert!(unfair.contains_key(&1));
+    assert!(!fair.contains_key(&1));
+    assert!(fair != unfair);
+}
+
+#[test]
+fn real_valued_propositions() {
+    let model = super::stable_model();
+    let formulas = vec!(parse_formula("x < 1 || 0.2 < y < 1.7", &model).unwrap());
+    let refined = model.refine_thresholds(&formulas.iter().collect::<Vec<_>>());
+    assert_eq!(vec!(0.0, 0.5, 1.0, 1.5, 2.0), refined.variables[0].thresholds);
+    assert_eq!(vec!(0.0, 0.2, 0.5, 1.5, 1.7, 2.0), refined.variables[1].thresholds);
+    let mut ctx = CheckerContext::new(refined.compile().unwrap()).unwrap();
+    //x < 1 covers 2 of 4 columns with 5 rows, 0.2 < y < 1.7 covers 3 of 5 rows
+    assert_eq!(10 + 6, check::<Order0>(&mut ctx, &formulas[0]).len());
+    //without refinement, only rectangles entirely inside the proposition are included
+    assert_eq!(3 + 3 - 1, check_formula(&formulas[0]).len());
+    //which is why validation rejects the formula for the unrefined model
+    assert_eq!(Err(CheckError::NotOnThreshold { variable: 0, value: 1.0 }),
+        validate::<Order0>(&model.compile().unwrap(), &formulas[0]));
+    assert_eq!(Ok(()), validate::<Order0>(&ctx.model, &formulas[0]));
+    assert_eq!(Ok(()), validate::<Order0>(&model.compile().unwrap(), &Atom(Value(0, LT, 3.0))));
+}
+
+#[test]
+fn threshold_indices_survive_refinement() {
+    let model = super::stable_model();
+    let formula = Atom(Equation(0, GT, 2)).with_values(&model);
+    assert_eq!(Atom(Value(0, GT, 1.5)), formula);
+    let refined = model.refine_thresholds(&[&formula, &Atom(Value(0, LT, 1.0))]);
+    let mut ctx = CheckerContext::new(refined.compile().unwrap()).unwrap();
+    assert_eq!(3, check::<Order0>(&mut ctx, &formula).len());
+}
+
+#[test]
+fn linear_proposition_semantics() {
+    let all = Atom(Linear(vec!((0, 1.0), (1, 1.0)), GT, 2.0, VertexSemantics::AllVertices));
+    let some = Atom(Linear(vec!((0, 1.0), (1, 1.0)), GT, 2.0, VertexSemantics::SomeVertex));
+    assert_eq!(1, check_formula(&all).len());
+    assert_eq!(6, check_formula(&some).len());
+    assert_eq!(check_formula(&all), check_text("x + y > 2"));
+}
+
+#[test]
+fn parameter_constraints() {
+    let model = read_bio("test", "VARS: x\nPARAMS: k, 0, 2; l, 0, 2\nTHRES: x: 0, 1, 2\nEQ: x = k - x\n").unwrap();
+    let mut ctx = CheckerContext::new(model.compile().unwrap()).unwrap();
+    let formula = parse_formula("2*k < 2.5", &model).unwrap();
+    assert_eq!(Atom(Parameters(vec!((0, 2.0)), LT, 2.5)), formula);
+    let expected = Order1(vec!(Clause(vec!(Interval(NEG_INFINITY, 1.25), Interval::one()))));
+    let result = check::<Order1>(&mut ctx, &formula);
+    assert_eq!(2, result.len());
+    assert!(result.values().all(|colors| *colors == expected));
+    //Order1 can't represent relations between parameters
+    let formula = parse_formula("k < l", &model).unwrap();
+    assert_eq!(Atom(Parameters(vec!((0, 1.0), (1, -1.0)), LT, 0.0)), formula);
+    assert_eq!(None, Order1::parameter_constraint(&ctx.model, &vec!((0, 1.0), (1, -1.0)), &LT, 0.0));
+    assert_eq!(Err(CheckError::UnsupportedConstraint(Parameters(vec!((0, 1.0), (1, -1.0)), LT, 0.0))),
+        validate::<Order1>(&ctx.model, &Or(Box::new(Atom(True)), Box::new(formula.clone()))));
+    assert!(check::<Order1>(&mut ctx, &formula).is_empty());
+}
+
+fn check_path(ctx: &mut CheckerContext<Order0>, path: &Vec<StateID>) {
+    for pair in path.windows(2) {
+        assert!(compute_directed_edges::<Order0>(ctx, &pair[0], &true).contains_key(&pair[1]));
+    }
+}
+
+#[test]
+fn reachability_witness() {
+    let model = super::stable_model();
+    let formula = parse_formula("EF (x > 0.5 && y > 0.5)", &model).unwrap();
+    let target = check_text("x > 0.5 && y > 0.5");
+    let mut ctx = CheckerContext::new(model.compile().unwrap()).unwrap();
+    let result = check::<Order0>(&mut ctx, &formula);
+    assert!(!result.is_empty());
+    for (state, colors) in &result {
+        let witness = witness::witness(&mut ctx, &formula, state, colors).unwrap();
+        assert_eq!(*state, witness.states[0]);
+        assert!(target.contains_key(witness.states.last().unwrap()));
+        assert_eq!(None, witness.loop_start);
+        check_path(&mut ctx, &witness.states);
+        assert_eq!(witness.states.len(), witness.export(&ctx.model).find("states").unwrap().as_array().unwrap().len());
+    }
+}
+
+#[test]
+fn lasso_witness_and_counterexample() {
+    let model = super::stable_model();
+    let formula = parse_formula("EG y > 0.5", &model).unwrap();
+    let valid = check_text("y > 0.5");
+    let mut ctx = CheckerContext::new(model.compile().unwrap()).unwrap();
+    for (state, colors) in check::<Order0>(&mut ctx, &formula) {
+        let witness = witness::witness(&mut ctx, &formula, &state, &colors).unwrap();
+        let loop_start = witness.loop_start.unwrap();
+        assert!(witness.states.iter().all(|s| valid.contains_key(s)));
+        let mut path = witness.states.clone();
+        path.push(witness.states[loop_start]);
+        check_path(&mut ctx, &path);
+    }
+    //every state that violates AF y < 0.5 has an EG y > 0.5 lasso
+    let af = parse_formula("AF y < 0.5", &model).unwrap();
+    let satisfied = check::<Order0>(&mut ctx, &af);
+    for state in check_text("True").keys().filter(|s| !satisfied.contains_key(s)) {
+        let counterexample = witness::counterexample(&mut ctx, &af, state, &Order0::tt()).unwrap();
+        assert!(counterexample.loop_start.is_some());
+    }
+}
+
+#[test]
+fn parallel_matches_sequential() {
+    let model = super::parametrised_model();
+    let compiled = model.compile().unwrap();
+    let texts = ["EX x > 1", "EF (x > 1.5 && y > 1)", "AF y < 1", "E [ x < 1.5 U y > 1.5 ]", "AG x < 1.5", "EG !(y < 0.5)",
+        "EG x > 0.5", "A [ y < 1.5 U x > 1 ]", "EF<=2 x > 1.5", "E [ x < 1.5 U<=1 y > 1.5 ]"];
+    for &fairness in &[Fairness::Disabled, Fairness::NoTransientLoops] {
+        for (formula, expected) in super::sequential_results_fair(&model, &texts, fairness) {
+            for threads in 1..4 {
+                let mut parallel_ctx = ParallelContext::new(compiled.clone(), threads).unwrap();
+                parallel_ctx.fairness = fairness;
+                super::assert_same_colors(&expected, &check_parallel::<Order1>(&parallel_ctx, &formula).unwrap());
+            }
+        }
+    }
+}
+
+#[test]
+fn parallel_fairness_removes_transient_self_loop() {
+    let model = read_bio("test", "VARS: x\nTHRES: x: 0, 0.5, 1.5, 2\nEQ: x = x - 1\n").unwrap();
+    let formula = parse_formula("EG (x > 0.5 && x < 1.5)", &model).unwrap();
+    let mut ctx = ParallelContext::<Order0>::new(model.compile().unwrap(), 2).unwrap();
+    assert!(check_parallel(&ctx, &formula).unwrap().contains_key(&1));
+    ctx.fairness = Fairness::NoTransientLoops;
+    assert!(!check_parallel(&ctx, &formula).unwrap().contains_key(&1));
+}
+
+#[test]
+fn parallel_rejects_hybrid_operators() {
+    let model = super::stable_model();
+    let formula = Bind("s".to_string(), Box::new(AX(Box::new(var("s")))));
+    let ctx = ParallelContext::<Order1>::new(model.compile().unwrap(), 2).unwrap();
+    assert_eq!(Err(CheckError::UnsupportedOperator(formula.clone())), check_parallel::<Order1>(&ctx, &formula));
+}
+
+#[test]
+fn distributed_matches_sequential() {
+    let model = super::parametrised_model();
+    let compiled = model.compile().unwrap();
+    let texts = ["!(x > 1)", "EX x > 1", "AF y < 1", "E [ x < 1.5 U y > 1.5 ]", "AG x < 1.5", "EG !(y < 0.5)",
+        "EG x > 0.5", "A [ y < 1.5 U x > 1 ]", "EF<=2 x > 1.5"];
+    for &fairness in &[Fairness::Disabled, Fairness::NoTransientLoops] {
+        let expected = super::sequential_results_fair(&model, &texts, fairness);
+        let formulas: Vec<Formula> = expected.iter().map(|&(ref formula, _)| formula.clone()).collect();
+        let socket = env::temp_dir().join(format!("ode_model_test_{}.sock", process::id()));
+        let _ = fs::remove_file(&socket);
+        let listener = UnixListener::bind(&socket).unwrap();
+        let workers: Vec<_> = (0..2).map(|index| {
+            let (socket, compiled, formulas) = (socket.clone(), compiled.clone(), formulas.clone());
+            thread::spawn(move || {
+                let formulas: Vec<&Formula> = formulas.iter().collect();
+                run_worker::<Order1>(&socket, index, 2, compiled, fairness, &formulas).unwrap();
+            })
+        }).collect();
+        let results = coordinate::<Order1>(&listener, 2, formulas.len()).unwrap();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+        let _ = fs::remove_file(&socket);
+        for ((_, expected), result) in expected.iter().zip(results.iter()) {
+            super::assert_same_colors(expected, result);
+        }
+    }
+}
+
+//Run the binary with the config at a relative path and read the colors of every formula.
+fn run_binary(config: &Json, name: &str) -> Vec<StateSet2<Order1>> {
+    //integration tests are in target/<profile>/deps, the binary is in target/<profile>
+    let binary = env::current_exe().unwrap().parent().unwrap().parent().unwrap().join("ode_model");
+    let path = format!("{}_{}.json", name, process::id());
+    File::create(&path).unwrap().write_all(config.to_string().as_bytes()).unwrap();
+    let output = Command::new(&binary).arg(&path).output().unwrap();
+    let _ = fs::remove_file(&path);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let json = Json::from_str(&String::from_utf8(output.stdout).unwrap()).unwrap();
+    let states: Vec<StateID> = json["states"].as_array().unwrap().iter().map(|s| {
+        s["id"].as_u64().unwrap() as StateID
+    }).collect();
+    let params: Vec<Order1> = json["params"].as_array().unwrap().iter().map(|p| {
+        Order1::from_json(p).unwrap()
+    }).collect();
+    json["results"].as_array().unwrap().iter().map(|result| {
+        result["data"].as_array().unwrap().iter().map(|item| {
+            let state = states[item["state"].as_u64().unwrap() as usize];
+            (state, params[item["param"].as_u64().unwrap() as usize].clone())
+        }).collect()
+    }).collect()
+}
+
+#[test]
+fn distributed_binary_matches_sequential() {
+    let texts = ["EX x > 1", "AF y < 1", "EG !(y < 0.5)", "E [ x < 1.5 U y > 1.5 ]"];
+    let config = |processes: u64| create_object(|map| {
+        map.write_item("model", &super::parametrised_model());
+        map.write_item("processes", &processes);
+        map.write_item("formulas", &texts.iter().map(|text| create_object(|map| {
+            map.write_item("first", &text.to_string());
+            map.write_item("second", &text.to_string());
+        })).collect::<Vec<Json>>());
+    });
+    let sequential = run_binary(&config(1), "ode_model_sequential_test");
+    let distributed = run_binary(&config(2), "ode_model_distributed_test");
+    assert_eq!(texts.len(), distributed.len());
+    for (expected, result) in sequential.iter().zip(distributed.iter()) {
+        super::assert_same_colors(expected, result);
+    }
+}
+
+#[test]
+fn bounded_reachability_grows_to_unbounded() {
+    let p = parse_formula("0.5 < x < 1.5 && 0.5 < y < 1.5", &super::stable_model()).unwrap();
+    let unbounded = check_formula(&EF(Box::new(p.clone())));
+    assert_eq!(check_formula(&p), check_formula(&BoundedEF(Box::new(p.clone()), 0)));
+    let mut previous = check_formula(&p);
+    for k in 1..20 {
+        let bounded = check_formula(&BoundedEF(Box::new(p.clone()), k));
+        assert!(previous.keys().all(|state| bounded.contains_key(state)));
+        assert!(bounded.keys().all(|state| unbounded.contains_key(state)));
+        previous = bounded;
+    }
+    assert_eq!(unbounded, previous);
+}
+
+#[test]
+fn distances_are_minimal_layers() {
+    let model = super::stable_model();
+    let p = parse_formula("0.5 < x < 1.5 && 0.5 < y < 1.5", &model).unwrap();
+    let mut ctx = CheckerContext::new(model.compile().unwrap()).unwrap();
+    let layers = distances::<Order0>(&mut ctx, &Atom(True), &p, None);
+    assert_eq!(check_formula(&p), layers[0]);
+    let mut seen: StateSet2<Order0> = ::std::collections::HashMap::new();
+    for (k, layer) in layers.iter().enumerate() {
+        assert!(layer.keys().all(|state| !seen.contains_key(state)));
+        seen.extend(layer.clone());
+        //states at distance k satisfy the bounded formula for k, but not for k - 1
+        let bounded = check_formula(&BoundedEF(Box::new(p.clone()), k));
+        assert!(layer.keys().all(|state| bounded.contains_key(state)));
+        if k > 0 {
+            let shorter = check_formula(&BoundedEF(Box::new(p.clone()), k - 1));
+            assert!(layer.keys().all(|state| !shorter.contains_key(state)));
+        }
+    }
+    assert_eq!(check_formula(&EF(Box::new(p))), seen);
+}
+
+fn var(name: &str) -> Formula {
+    Var(name.to_string())
+}
+
+#[test]
+fn hybrid_operators_on_single_states() {
+    let p = parse_formula("x > 1.5", &super::stable_model()).unwrap();
+    let all = check_formula(&Atom(True));
+    assert_eq!(all, check_formula(&Exists("s".to_string(), Box::new(var("s")))));
+    assert_eq!(all, check_formula(&Bind("s".to_string(), Box::new(EF(Box::new(var("s")))))));
+    assert_eq!(check_formula(&p), check_formula(&Bind("s".to_string(), Box::new(At("s".to_string(), Box::new(p.clone()))))));
+    let exists = Exists("s".to_string(), Box::new(And(Box::new(p.clone()), Box::new(var("s")))));
+    assert_eq!(check_formula(&p), check_formula(&exists));
+}
+
+#[test]
+fn bound_self_loops() {
+    let model = super::parametrised_model();
+    let formula = parse_formula("bind s: EX s", &model).unwrap();
+    let compiled = model.compile().unwrap();
+    let bounds = Order1::model_bounds(&compiled);
+    let mut ctx = CheckerContext::new(compiled).unwrap();
+    let result = check::<Order1>(&mut ctx, &formula);
+    for state in 0..16 {
+        let self_loop = compute_directed_edges::<Order1>(&mut ctx, &state, &true).get(&state)
+            .map(|c| c.and(&bounds)).unwrap_or(Order1::ff());
+        assert_eq!(self_loop, result.get(&state).cloned().unwrap_or(Order1::ff()));
+    }
+}
+
+#[test]
+fn unbound_state_variables() {
+    let formula = EX(Box::new(var("s")));
+    assert!(check_formula(&formula).is_empty());
+    assert!(check_formula(&At("s".to_string(), Box::new(Atom(True)))).is_empty());
+    let model = super::stable_model().compile().unwrap();
+    assert_eq!(Err(CheckError::UnboundVariable("s".to_string())), validate::<Order0>(&model, &formula));
+    assert_eq!(Ok(()), validate::<Order0>(&model, &Bind("s".to_string(), Box::new(formula))));
+}
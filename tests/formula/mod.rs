@@ -0,0 +1,109 @@
+extern crate ode_model;
+
+use ode_model::OdeModel;
+use ode_model::bio::read_bio;
+use ode_model::parser::ParseError;
+use ode_model::formula::Formula::*;
+use ode_model::formula::Proposition::*;
+use ode_model::formula::CompareOp::*;
+use ode_model::formula::VertexSemantics::*;
+use ode_model::formula::parser::parse_formula;
+
+fn model() -> OdeModel {
+    read_bio("test", "VARS: x, y\nTHRES: x: 0, 1, 3.5, 5\nTHRES: y: 0, 2, 4\nEQ: x = y\nEQ: y = -x\n").unwrap()
+}
+
+#[test]
+fn parse_exists_until() {
+    let formula = parse_formula("E [ x < 1 U y > 2 ]", &model()).unwrap();
+    assert_eq!(EU(Box::new(Atom(Value(0, LT, 1.0))), Box::new(Atom(Value(1, GT, 2.0)))), formula);
+}
+
+#[test]
+fn parse_bounded_operators() {
+    let formula = parse_formula("EF<=10 x > 3.5", &model()).unwrap();
+    assert_eq!(BoundedEF(Box::new(Atom(Value(0, GT, 3.5))), 10), formula);
+    let formula = parse_formula("E [ x < 1 U<=2 y > 2 ]", &model()).unwrap();
+    assert_eq!(BoundedEU(Box::new(Atom(Value(0, LT, 1.0))), Box::new(Atom(Value(1, GT, 2.0))), 2), formula);
+    assert!(parse_formula("AF<=2 x > 1", &model()).is_err());
+    assert!(parse_formula("A [ x < 1 U<=2 y > 2 ]", &model()).is_err());
+    assert!(parse_formula("EF<=1.5 x > 1", &model()).is_err());
+}
+
+#[test]
+fn parse_hybrid_operators() {
+    let stable = Bind("s".to_string(), Box::new(AX(Box::new(Var("s".to_string())))));
+    assert_eq!(stable, parse_formula("bind s: AX s", &model()).unwrap());
+    assert_eq!(stable, parse_formula("↓s. AX s", &model()).unwrap());
+    let formula = parse_formula("∃s. at s: x > 1 && EF s", &model()).unwrap();
+    assert_eq!(Exists("s".to_string(), Box::new(At("s".to_string(), Box::new(And(
+        Box::new(Atom(Value(0, GT, 1.0))),
+        Box::new(EF(Box::new(Var("s".to_string()))))
+    ))))), formula);
+    assert!(formula.free_variables().is_empty());
+    assert!(formula.is_hybrid());
+    assert_eq!(vec!["s".to_string()], EX(Box::new(Var("s".to_string()))).free_variables());
+    assert_eq!(ParseError { line: 1, column: 4, message: "Unbound state variable: s".to_string() },
+        parse_formula("at s: x > 1", &model()).unwrap_err());
+    assert_eq!(ParseError { line: 1, column: 6, message: "State variable x is also a model variable or parameter".to_string() },
+        parse_formula("bind x: EX x > 1", &model()).unwrap_err());
+}
+
+#[test]
+fn parse_derived_operators() {
+    let formula = parse_formula("EF (x > 3.5 && AG 2 > y)", &model()).unwrap();
+    assert_eq!(EF(Box::new(And(
+        Box::new(Atom(Value(0, GT, 3.5))),
+        Box::new(AG(Box::new(Atom(Value(1, LT, 2.0)))))
+    ))), formula);
+}
+
+#[test]
+fn parse_operator_precedence() {
+    let formula = parse_formula("!x < 1 && True || False", &model()).unwrap();
+    assert_eq!(Or(
+        Box::new(And(Box::new(Not(Box::new(Atom(Value(0, LT, 1.0))))), Box::new(Atom(True)))),
+        Box::new(Atom(False))
+    ), formula);
+}
+
+#[test]
+fn parse_implication_is_right_associative() {
+    let formula = parse_formula("AX True => EG False => x < 1", &model()).unwrap();
+    assert_eq!(Implies(
+        Box::new(AX(Box::new(Atom(True)))),
+        Box::new(Implies(Box::new(EG(Box::new(Atom(False)))), Box::new(Atom(Value(0, LT, 1.0)))))
+    ), formula);
+}
+
+#[test]
+fn parse_formula_errors() {
+    assert_eq!(ParseError { line: 1, column: 5, message: "Unknown variable or parameter: z".to_string() },
+        parse_formula("EF (z > 1)", &model()).unwrap_err());
+    assert_eq!(ParseError { line: 1, column: 4, message: "Empty range of variable x".to_string() },
+        parse_formula("EF 3 < x < 2", &model()).unwrap_err());
+    assert_eq!(ParseError { line: 1, column: 17, message: "Expected RightBracket, found End".to_string() },
+        parse_formula("E [ x < 1 U True", &model()).unwrap_err());
+}
+
+#[test]
+fn parse_comparisons_and_ranges() {
+    let model = model();
+    assert_eq!(Atom(Value(0, LE, 3.7)), parse_formula("x <= 3.7", &model).unwrap());
+    assert_eq!(Atom(Value(1, GE, 1.0)), parse_formula("1 <= y", &model).unwrap());
+    assert_eq!(Atom(Range(0, 2.0, 5.0)), parse_formula("2 < x < 5", &model).unwrap());
+    assert_eq!(Atom(Range(1, 0.5, 3.0)), parse_formula("3 >= y > 0.5", &model).unwrap());
+}
+
+#[test]
+fn parse_linear_propositions() {
+    let model = model();
+    assert_eq!(Atom(Linear(vec!((0, 1.0), (1, 2.0)), GT, 5.0, AllVertices)),
+        parse_formula("x + 2*y > 5", &model).unwrap());
+    assert_eq!(Atom(Linear(vec!((0, 1.0), (1, -1.0)), GT, 0.0, AllVertices)),
+        parse_formula("x > y", &model).unwrap());
+    //a single variable is normalised to a value proposition
+    assert_eq!(Atom(Value(1, LT, 1.5)), parse_formula("-2*y + 1 > -2", &model).unwrap());
+    assert_eq!(ParseError { line: 1, column: 1, message: "Proposition does not contain a variable".to_string() },
+        parse_formula("x - x < 1", &model).unwrap_err());
+}
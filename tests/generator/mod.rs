@@ -10,6 +10,9 @@ use ode_model::model::evaluable::Function;
 use ode_model::model::StateID;
 
 use ode_model::generator::compute_directed_edges;
+use ode_model::generator::{initial_states, initial_states_part};
+use ode_model::formula::Proposition;
+use ode_model::formula::CompareOp;
 
 use ode_model::parameters::Colors;
 use ode_model::parameters::order_0::Order0;
@@ -180,6 +183,22 @@ fn create_model(a: f64, b: f64, c: f64, d: f64) -> Model {
     )
 }
 
+#[test]
+fn initial_states_split_into_parts() {
+    let model = create_model(1.0, 1.0, 1.0, 1.0);
+    for prop in &[Proposition::True, Proposition::Equation(0, CompareOp::GT, 1)] {
+        let all = initial_states::<Order0>(&model, prop);
+        let mut union = HashMap::new();
+        for index in 0..2 {
+            let part = initial_states_part::<Order0>(&model, prop, index, 2);
+            assert!(part.keys().all(|state| state % 2 == index));
+            union.extend(part);
+        }
+        assert_eq!(all, union);
+    }
+    assert_eq!(vec![1], initial_states_part::<Order0>(&model, &Proposition::True, 1, 2).keys().cloned().collect::<Vec<StateID>>());
+}
+
 fn generator_simple_test<C: Colors + Debug>(
     a: f64, b: f64, c: f64, d: f64,
     s1: Vec<StateID>, s2: Vec<StateID>, s3: Vec<StateID>,
@@ -4,6 +4,80 @@ extern crate json_utils;
 
 mod parameters;
 mod generator;
+mod approximation;
+mod validation;
+mod names;
+mod parser;
+mod bio;
+mod sbml;
+mod formula;
+mod checker;
+mod symbolic;
+mod analysis;
+mod reachability;
+
+use ode_model::OdeModel;
+use ode_model::bio::read_bio;
+use ode_model::formula::Formula;
+use ode_model::formula::parser::parse_formula;
+use ode_model::generator::StateSet2;
+use ode_model::model::StateID;
+use ode_model::checker::check;
+use ode_model::checker::CheckerContext;
+use ode_model::checker::Fairness;
+use ode_model::parameters::order_1::Order1;
+
+//3 x 3 rectangles with a single stable state in the middle one.
+pub fn stable_model() -> OdeModel {
+    read_bio("test", "
+VARS: x, y
+THRES: x: 0, 0.5, 1.5, 2
+THRES: y: 0, 0.5, 1.5, 2
+EQ: x = 1 - x
+EQ: y = x - y
+").unwrap()
+}
+
+//4 x 4 rectangles where the direction of x depends on the parameter k.
+pub fn parametrised_model() -> OdeModel {
+    read_bio("test", "
+VARS: x, y
+PARAMS: k, 0, 2
+THRES: x: 0, 0.5, 1, 1.5, 2
+THRES: y: 0, 0.5, 1, 1.5, 2
+EQ: x = k - x
+EQ: y = x - y
+").unwrap()
+}
+
+//Parse the formulas and check them with the sequential checker, as a reference for other checkers.
+pub fn sequential_results(model: &OdeModel, texts: &[&str]) -> Vec<(Formula, StateSet2<Order1>)> {
+    sequential_results_fair(model, texts, Fairness::Disabled)
+}
+
+//Order1 equality is containment in one direction, so results are compared both ways.
+pub fn assert_same_colors(expected: &StateSet2<Order1>, actual: &StateSet2<Order1>) {
+    let mut expected_states: Vec<&StateID> = expected.keys().collect();
+    let mut actual_states: Vec<&StateID> = actual.keys().collect();
+    expected_states.sort();
+    actual_states.sort();
+    assert_eq!(expected_states, actual_states);
+    for (state, colors) in expected {
+        assert!(colors == &actual[state] && &actual[state] == colors,
+            "State {}: {:?} != {:?}", state, colors, actual[state]);
+    }
+}
+
+pub fn sequential_results_fair(model: &OdeModel, texts: &[&str], fairness: Fairness)
+    -> Vec<(Formula, StateSet2<Order1>)> {
+    let mut ctx = CheckerContext::new(model.compile().unwrap()).unwrap();
+    ctx.fairness = fairness;
+    texts.iter().map(|text| {
+        let formula = parse_formula(text, model).unwrap();
+        let result = check::<Order1>(&mut ctx, &formula);
+        (formula, result)
+    }).collect()
+}
 
 //use rustc_serialize::json::Json;
 //use rustc_serialize::json::ToJson;
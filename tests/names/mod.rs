@@ -0,0 +1,48 @@
+extern crate ode_model;
+extern crate rustc_serialize;
+extern crate json_utils;
+
+use rustc_serialize::json::Json;
+use rustc_serialize::json::ToJson;
+use json_utils::FromJson;
+use ode_model::*;
+
+static NAMED_MODEL: &'static str = r#"{
+  "name": "Named", "parameters": [{ "name": "k", "range": { "min": 0, "max": 1 }}],
+  "variables": [
+    { "name": "x", "range": { "min": 0, "max": 10 }, "thresholds": [0, 10],
+      "equation": [{ "constant": 1, "variables": ["y"], "parameters": ["k"], "evaluables": [
+        { "type": "step", "variable": "x", "theta": 5, "a": 0, "b": 1 }
+      ]}]
+    },
+    { "name": "y", "range": { "min": 0, "max": 10 }, "thresholds": [0, 10],
+      "equation": [{ "constant": -1, "variableIndices": [1] }]
+    }
+  ]
+}"#;
+
+#[test]
+fn names_resolved_to_indices() {
+    let model = OdeModel::from_json(&Json::from_str(NAMED_MODEL).unwrap()).unwrap();
+    let ref summand = model.variables[0].equation[0];
+    assert_eq!(vec!(1), summand.variable_indices);
+    assert_eq!(vec!(0), summand.parameter_indices);
+    assert_eq!(Evaluable::Step { variable_index: 0, theta: 5.0, a: 0.0, b: 1.0 }, summand.evaluables[0]);
+    assert_eq!(vec!(1), model.variables[1].equation[0].variable_indices);
+}
+
+#[test]
+fn names_round_trip() {
+    let model = OdeModel::from_json(&Json::from_str(NAMED_MODEL).unwrap()).unwrap();
+    let named = model.to_json_with_names();
+    assert_eq!(Some(&Json::Array(vec!("y".to_json()))),
+        named.search("equation").and_then(|e| e[0].find("variables")));
+    assert_eq!(model, OdeModel::from_json(&named).unwrap());
+}
+
+#[test]
+#[should_panic(expected = "Unknown variable name: z")]
+fn unknown_name() {
+    let json = NAMED_MODEL.replace("\"variables\": [\"y\"]", "\"variables\": [\"z\"]");
+    OdeModel::from_json(&Json::from_str(&json).unwrap()).unwrap();
+}
@@ -1,11 +1,16 @@
 extern crate ode_model;
+extern crate rustc_serialize;
+extern crate json_utils;
 
+use ode_model::bio::read_bio;
 use ode_model::parameters::Colors;
 use ode_model::parameters::order_1::Order1;
 use ode_model::parameters::order_1::Clause;
 use ode_model::types::Interval;
 use std::f64::INFINITY;
 use std::f64::NEG_INFINITY;
+use rustc_serialize::json::{Json, ToJson};
+use json_utils::FromJson;
 
 #[test]
 fn order_1_colors_emptiness() {
@@ -74,4 +79,24 @@ fn order_1_colors_not() {
         Clause(vec![Interval(1.0, 2.0)]),
         Clause(vec![Interval(3.0, INFINITY)])
         ]), c3.not()];
-}
\ No newline at end of file
+}
+
+#[test]
+fn order_1_colors_json_round_trip() {
+    let colors = Order1(vec![
+        Clause(vec![Interval(NEG_INFINITY, 0.0), Interval(1.5, INFINITY)]),
+        Clause(vec![Interval(2.0, 3.0)])
+    ]);
+    let json = Json::from_str(&colors.to_json().to_string()).unwrap();
+    assert_eq![colors, Order1::from_json(&json).unwrap()];
+    assert![Order1::from_json(&Json::from_str("[[[1.0]]]").unwrap()).is_err()];
+}
+
+#[test]
+fn order_1_divide_repeated_parameter() {
+    //both parametrised summands use k, so the equation is still linear in one parameter: k - 1 at x = 1
+    let model = read_bio("test", "VARS: x\nPARAMS: k, 0, 2\nTHRES: x: 0, 1, 2\nEQ: x = 2*k - k*x - 1\n").unwrap();
+    let (negative, positive) = Order1::divide(&model.compile().unwrap(), &0, &vec![1]).unwrap();
+    assert_eq!(Order1(vec![Clause(vec![Interval(NEG_INFINITY, 1.0)])]), negative);
+    assert_eq!(Order1(vec![Clause(vec![Interval(1.0, INFINITY)])]), positive);
+}
@@ -3,6 +3,9 @@ extern crate ode_model;
 extern crate z3;
 use self::z3::*;
 
+use ode_model::bio::read_bio;
+use ode_model::model::CompileError;
+use ode_model::formula::CompareOp::*;
 use ode_model::parameters::Colors;
 use ode_model::parameters::order_n::*;
 
@@ -11,6 +14,8 @@ pub fn test_all(z3: &Context) {
     order_n_colors_and(z3);
     order_n_colors_or(z3);
     order_n_colors_not(z3);
+    order_n_large_constraints();
+    order_n_divide_uses_own_thresholds();
 }
 
 pub fn order_n_colors_emptiness() {
@@ -68,4 +73,31 @@ pub fn order_n_colors_not(z3: &Context) {
     assert![lt2.and(&lt3.not()).is_empty()];
     assert![gt2.and(&gt3.not()).is_not_empty()];
     assert![lt3.and(&lt2.not()).is_not_empty()];
-}
\ No newline at end of file
+}
+
+pub fn order_n_large_constraints() {
+    let model = read_bio("test", "VARS: x\nPARAMS: k, 0, 10000\nTHRES: x: 0, 1\nEQ: x = k - x\n").unwrap()
+        .compile().unwrap();
+    let above = OrderN::parameter_constraint(&model, &vec![(0, 1.0)], &GT, 5000.0).unwrap();
+    let below = OrderN::parameter_constraint(&model, &vec![(0, 1.0)], &LT, 4000.0).unwrap();
+    assert![above.and(&below).is_empty()];
+    assert![above.and(&below.not()).is_not_empty()];
+    assert![OrderN::parameter_constraint(&model, &vec![(0, 1.0)], &GT, 1e300).is_none()];
+}
+
+pub fn order_n_divide_uses_own_thresholds() {
+    //x = k - y, so in the upper vertex of y the sign changes at k = 5000, not at a threshold of x
+    let model = read_bio("test", "VARS: x, y\nPARAMS: k, 0, 10000\nTHRES: x: 0, 1\nTHRES: y: 0, 5000\n\
+        EQ: x = k - y\nEQ: y = 1\n").unwrap().compile().unwrap();
+    let (negative, positive) = OrderN::divide(&model, &0, &vec![1, 1]).unwrap();
+    let below = OrderN::parameter_constraint(&model, &vec![(0, 1.0)], &LT, 4999.0).unwrap();
+    let above = OrderN::parameter_constraint(&model, &vec![(0, 1.0)], &GT, 5001.0).unwrap();
+    assert![positive.and(&below).is_empty()];
+    assert![positive.and(&above).is_not_empty()];
+    assert![negative.and(&above).is_empty()];
+    assert![negative.and(&below).is_not_empty()];
+
+    let huge = read_bio("test", "VARS: x, y\nPARAMS: k, 0, 1\nTHRES: x: 0, 1\nTHRES: y: 0, 1e300\n\
+        EQ: x = k - y\nEQ: y = 1\n").unwrap().compile().unwrap();
+    assert_eq![Err(CompileError::NotRepresentable { variable: 0 }), OrderN::divide(&huge, &0, &vec![1, 1])];
+}
@@ -0,0 +1,62 @@
+extern crate ode_model;
+extern crate rustc_serialize;
+extern crate json_utils;
+
+use rustc_serialize::json::Json;
+use json_utils::FromJson;
+use ode_model::*;
+use ode_model::parser::*;
+
+fn parser() -> EquationParser {
+    EquationParser::new(vec!("x".to_string(), "y".to_string()), vec!("k1".to_string()))
+}
+
+#[test]
+fn parse_simple_equation() {
+    let (target, equation) = parser().parse_equation("dx/dt = k1*y*hillp(x, 2.0, 5, 0, 1) - 0.5*x").unwrap();
+    assert_eq!(Some(0), target);
+    assert_eq!(vec!(
+        Summand { constant: 1.0, variable_indices: vec!(1), parameter_indices: vec!(0), evaluables: vec!(
+            Evaluable::Hill { variable_index: 0, theta: 2.0, n: 5.0, a: 0.0, b: 1.0 }
+        )},
+        Summand { constant: -0.5, variable_indices: vec!(0), parameter_indices: vec!(), evaluables: vec!() }
+    ), equation);
+}
+
+#[test]
+fn parse_expands_parentheses() {
+    let (target, equation) = parser().parse_equation("-(x + 2) * y / 4").unwrap();
+    assert_eq!(None, target);
+    assert_eq!(vec!(
+        Summand { constant: -0.25, variable_indices: vec!(0, 1), parameter_indices: vec!(), evaluables: vec!() },
+        Summand { constant: -0.5, variable_indices: vec!(1), parameter_indices: vec!(), evaluables: vec!() }
+    ), equation);
+}
+
+#[test]
+fn parse_error_position() {
+    let error = parser().parse_system("dx/dt = y\n\ndy/dt = 2 * z").unwrap_err();
+    assert_eq!(ParseError { line: 3, column: 13, message: "Unknown identifier: z".to_string() }, error);
+}
+
+#[test]
+fn write_and_parse_equation() {
+    let p = parser();
+    let (_, equation) = p.parse_equation("-x*k1 + 3*step(y, 2, 0, 1) - approx(x, 0, 1, 5, 2)").unwrap();
+    let text = write_equation(&equation, &p.variables, &p.parameters);
+    assert_eq!("-x*k1 + 3*step(y, 2, 0, 1) - approx(x, 0, 1, 5, 2)", text);
+    assert_eq!(equation, p.parse_equation(&text).unwrap().1);
+}
+
+#[test]
+fn equation_text_in_json() {
+    let json = Json::from_str(r#"{
+      "name": "Text", "parameters": [],
+      "variables": [
+        { "name": "x", "range": { "min": 0, "max": 10 }, "thresholds": [0, 10], "equationText": "2 - x" }
+      ]
+    }"#).unwrap();
+    let model = OdeModel::from_json(&json).unwrap();
+    assert_eq!(2, model.variables[0].equation.len());
+    assert_eq!(vec!(0), model.variables[0].equation[1].variable_indices);
+}
@@ -0,0 +1,76 @@
+use std::collections::HashMap;
+
+use ode_model::formula::parser::parse_formula;
+use ode_model::formula::Formula::*;
+use ode_model::formula::Proposition;
+use ode_model::generator::StateSet2;
+use ode_model::generator::initial_states;
+use ode_model::checker::check;
+use ode_model::checker::CheckerContext;
+use ode_model::parameters::Colors;
+use ode_model::parameters::order_1::Order1;
+use ode_model::reachability::{ReachOptions, reachable, forward_reachable, backward_reachable};
+
+fn bounded(model: &::ode_model::model::Model, set: StateSet2<Order1>) -> StateSet2<Order1> {
+    let bounds = Order1::model_bounds(model);
+    set.into_iter().map(|(s, c)| (s, c.and(&bounds))).filter(|&(_, ref c)| c.is_not_empty()).collect()
+}
+
+#[test]
+fn backward_reachability_matches_ef() {
+    let model = super::parametrised_model();
+    let compiled = model.compile().unwrap();
+    let target = parse_formula("x > 1.5 && y > 1.5", &model).unwrap();
+    let mut ctx = CheckerContext::new(compiled.clone()).unwrap();
+    let expected = check::<Order1>(&mut ctx, &EF(Box::new(target.clone())));
+    let initial = check::<Order1>(&mut ctx, &target);
+    let result = backward_reachable(&mut ctx, initial);
+    super::assert_same_colors(&bounded(&compiled, expected), &bounded(&compiled, result));
+}
+
+#[test]
+fn forward_reachability_is_closed() {
+    let model = super::parametrised_model().compile().unwrap();
+    let mut ctx = CheckerContext::new(model.clone()).unwrap();
+    let mut initial = HashMap::new();
+    initial.insert(0, Order1::model_bounds(&model));
+    let result = forward_reachable(&mut ctx, initial.clone());
+    //no new states or colors are reachable from the result
+    let again = forward_reachable(&mut ctx, result.clone());
+    super::assert_same_colors(&bounded(&model, result.clone()), &bounded(&model, again));
+    assert!(result.len() > 1);
+}
+
+#[test]
+fn layers_depth_and_target() {
+    let model = super::parametrised_model().compile().unwrap();
+    let mut ctx = CheckerContext::new(model.clone()).unwrap();
+    let mut initial = HashMap::new();
+    initial.insert(0, Order1::model_bounds(&model));
+    let mut layers = vec![];
+    let full = reachable(&mut ctx, initial.clone(), &ReachOptions::forward(), |depth, layer| {
+        layers.push((depth, layer.len()));
+    });
+    assert_eq!(full.depth + 1, layers.len());
+    assert_eq!((0, 1), layers[0]);
+    assert!(layers.iter().all(|&(_, size)| size > 0));
+
+    let mut bounded_options = ReachOptions::forward();
+    bounded_options.max_depth = Some(1);
+    let one_step = reachable(&mut ctx, initial.clone(), &bounded_options, |_, _| {});
+    assert_eq!(1, one_step.depth);
+    assert!(one_step.states.len() <= full.states.len());
+
+    //the initial state is its own target, so nothing else is explored
+    let mut target_options = ReachOptions::forward();
+    target_options.target = Some(&initial);
+    let hit = reachable(&mut ctx, initial.clone(), &target_options, |_, _| {});
+    assert_eq!(0, hit.depth);
+    assert!(Order1::model_bounds(&model) == hit.hit && hit.hit == Order1::model_bounds(&model));
+
+    let everything: StateSet2<Order1> = initial_states(&model, &Proposition::True);
+    let mut everything_options = ReachOptions::backward();
+    everything_options.within = Some(&everything);
+    super::assert_same_colors(&bounded(&model, backward_reachable(&mut ctx, initial.clone())),
+        &bounded(&model, reachable(&mut ctx, initial, &everything_options, |_, _| {}).states));
+}
@@ -0,0 +1,68 @@
+extern crate ode_model;
+
+use std::fs::File;
+use std::io::Read;
+
+use ode_model::*;
+use ode_model::sbml::*;
+
+fn read_sample() -> String {
+    let mut text = String::new();
+    File::open("tests/input_files/sample_model.xml").unwrap().read_to_string(&mut text).unwrap();
+    text
+}
+
+#[test]
+fn import_michaelis_menten() {
+    let model = import_sbml(&read_sample(), &[], &Range { min: 0.0, max: 20.0 }).unwrap();
+    assert_eq!("Michaelis Menten", model.name);
+    assert_eq!(2, model.variables.len());
+    let hill = Evaluable::Hill { variable_index: 0, theta: 4.0, n: 1.0, a: 0.0, b: 1.0 };
+    assert_eq!(vec!(
+        Summand { constant: -6.0, variable_indices: vec!(), parameter_indices: vec!(), evaluables: vec!(hill.clone()) }
+    ), model.variables[0].equation);
+    assert_eq!(vec!(
+        Summand { constant: 12.0, variable_indices: vec!(), parameter_indices: vec!(), evaluables: vec!(hill) },
+        Summand { constant: -0.1, variable_indices: vec!(1), parameter_indices: vec!(), evaluables: vec!() }
+    ), model.variables[1].equation);
+}
+
+#[test]
+fn import_with_free_parameter() {
+    let kd = Parameter { name: "kd".to_string(), range: Range { min: 0.0, max: 1.0 } };
+    let model = import_sbml(&read_sample(), &[kd], &Range { min: 0.0, max: 20.0 }).unwrap();
+    assert_eq!(vec!(0), model.variables[1].equation[1].parameter_indices);
+}
+
+#[test]
+fn local_parameter_shadows_free_parameter() {
+    let text = read_sample().replace("</math>\n        </kineticLaw>\n      </reaction>\n    </listOfReactions>",
+        "</math>\n        <listOfLocalParameters><localParameter id=\"kd\" value=\"0.2\"/></listOfLocalParameters>\n\
+        </kineticLaw>\n      </reaction>\n    </listOfReactions>");
+    let kd = Parameter { name: "kd".to_string(), range: Range { min: 0.0, max: 1.0 } };
+    let model = import_sbml(&text, &[kd], &Range { min: 0.0, max: 20.0 }).unwrap();
+    assert!(model.variables[1].equation[1].parameter_indices.is_empty());
+    assert_eq!(-0.2, model.variables[1].equation[1].constant);
+}
+
+#[test]
+fn unsupported_operator() {
+    let text = read_sample().replace("<times/><ci> kd </ci>", "<exp/><ci> kd </ci>");
+    assert_eq!(Err(SbmlError::Unsupported("operator exp with 2 arguments".to_string())),
+        import_sbml(&text, &[], &Range { min: 0.0, max: 20.0 }));
+}
+
+#[test]
+fn import_with_compartment_volume() {
+    let text = read_sample().replace("size=\"1\"", "size=\"2\"");
+    let model = import_sbml(&text, &[], &Range { min: 0.0, max: 20.0 }).unwrap();
+    //the conversion rate is proportional to the volume, so the concentration change stays the same
+    assert_eq!(-6.0, model.variables[0].equation[0].constant);
+    assert_eq!(12.0, model.variables[1].equation[0].constant);
+    assert_eq!(-0.05, model.variables[1].equation[1].constant);
+    //amounts are not divided by the volume
+    let text = text.replace("<species id=\"P\"", "<species id=\"P\" hasOnlySubstanceUnits=\"true\"");
+    let model = import_sbml(&text, &[], &Range { min: 0.0, max: 20.0 }).unwrap();
+    assert_eq!(24.0, model.variables[1].equation[0].constant);
+    assert_eq!(-0.1, model.variables[1].equation[1].constant);
+}
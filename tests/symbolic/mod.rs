@@ -0,0 +1,79 @@
+use std::collections::HashMap;
+
+use ode_model::generator::StateSet2;
+use ode_model::generator::initial_states;
+use ode_model::formula::Proposition;
+use ode_model::checker::CheckerContext;
+use ode_model::parameters::order_0::Order0;
+use ode_model::parameters::order_1::{Order1, Clause};
+use ode_model::types::Interval;
+use ode_model::symbolic::{StateSet, SymbolicSet, check_with};
+use ode_model::symbolic::bdd::StateEncoding;
+use ode_model::model::StateID;
+
+static FORMULAS: [&'static str; 9] = [
+    "x > 1 && !(y < 1)", "x < 0.5 || y > 2", "EX x > 1", "AX y < 1.5", "EF (x > 1.5 && y > 1)",
+    "AF y < 1", "E [ x < 1.5 U y > 1.5 ]", "AG x < 1.5", "EG !(y < 0.5)"
+];
+
+#[test]
+fn symbolic_matches_explicit() {
+    let model = super::parametrised_model();
+    let compiled = model.compile().unwrap();
+    let mut ctx = CheckerContext::new(compiled.clone()).unwrap();
+    for (formula, expected) in super::sequential_results(&model, &FORMULAS) {
+        let symbolic: SymbolicSet<Order1> = check_with(&mut ctx, &formula);
+        super::assert_same_colors(&expected, &symbolic.to_explicit(&compiled));
+        let explicit: StateSet2<Order1> = check_with(&mut ctx, &formula);
+        super::assert_same_colors(&expected, &explicit);
+    }
+}
+
+#[test]
+fn uniform_regions_are_compact() {
+    let model = super::stable_model().compile().unwrap();
+    let all: SymbolicSet<Order0> = SymbolicSet::from_proposition(&model, &Proposition::True);
+    assert_eq!(1, all.groups.len());
+    assert_eq!(9, StateEncoding::new(&model).decode(&model, &all.states(&model)).len());
+    //3 x 3 rectangles, encoded by 2 + 2 bits
+    assert!(all.groups[0].0.size() <= 4);
+    let none = all.not(&model);
+    assert!(none.is_empty());
+    let explicit: StateSet2<Order0> = initial_states(&model, &Proposition::True);
+    assert_eq!(explicit, SymbolicSet::from_explicit(&model, &explicit).to_explicit(&model));
+}
+
+#[test]
+fn nested_colors_are_not_grouped() {
+    let model = super::parametrised_model().compile().unwrap();
+    let small = Order1(vec!(Clause(vec!(Interval(0.0, 1.0)))));
+    let large = Order1(vec!(Clause(vec!(Interval(0.0, 2.0)))));
+    let mut explicit = HashMap::new();
+    explicit.insert(0, small.clone());
+    explicit.insert(1, large.clone());
+    let symbolic = SymbolicSet::from_explicit(&model, &explicit);
+    assert_eq!(2, symbolic.groups.len());
+    super::assert_same_colors(&explicit, &symbolic.to_explicit(&model));
+    let mut left = HashMap::new();
+    left.insert(0, small);
+    let mut right = HashMap::new();
+    right.insert(1, large);
+    let union = SymbolicSet::from_explicit(&model, &left).or(&SymbolicSet::from_explicit(&model, &right));
+    super::assert_same_colors(&explicit, &union.to_explicit(&model));
+}
+
+#[test]
+fn neighbours_match_the_model() {
+    //4 x 4 rectangles, so both coordinates use all values of their bits
+    let model = super::parametrised_model().compile().unwrap();
+    let encoding = StateEncoding::new(&model);
+    for state in 0..16 {
+        let bdd = encoding.state(&model, &state);
+        for d in 0..2 {
+            let lower: Vec<StateID> = model.lower_neighbour(&state, &d).into_iter().collect();
+            let upper: Vec<StateID> = model.upper_neighbour(&state, &d).into_iter().collect();
+            assert_eq!(lower, encoding.decode(&model, &encoding.lower_neighbours(&bdd, d)));
+            assert_eq!(upper, encoding.decode(&model, &encoding.upper_neighbours(&bdd, d)));
+        }
+    }
+}
@@ -0,0 +1,136 @@
+extern crate ode_model;
+
+use ode_model::*;
+use ode_model::validation::IssueKind;
+use ode_model::validation::Severity;
+use ode_model::model::CompileError;
+use ode_model::parameters::capabilities;
+use ode_model::parameters::Colors;
+use ode_model::parameters::order_1::Order1;
+use ode_model::checker::CheckerContext;
+
+#[test]
+fn report_lists_all_issues() {
+    let model = OdeModel { name: "Test".to_string(),
+        parameters: vec!(Parameter { name: "p1".to_string(), range: Range { min: 2.0, max: 1.0 }}),
+        variables: vec!(
+            Variable { name: "var1".to_string(), range: Range { min: 0.0, max: 15.0 },
+                thresholds: vec!(0.0, 15.0), var_points: None,
+                equation: vec!()
+            },
+            Variable { name: "var2".to_string(), range: Range { min: 0.0, max: 15.0 },
+                thresholds: vec!(0.0, 15.0), var_points: None,
+                equation: vec!(Summand { constant: 1.0, variable_indices: vec!(0),
+                    parameter_indices: vec!(3), evaluables: vec!()
+                }, Summand { constant: 1.0, variable_indices: vec!(),
+                    parameter_indices: vec!(), evaluables: vec!(
+                        Evaluable::Hill { variable_index: 2, theta: 1.2, n: 5.0, a: 1.0, b: 2.0 },
+                        Evaluable::RampApproximation { variable_index: 1, approximation: vec!() }
+                    )
+                })
+            }
+        )
+    };
+    let report = model.validate();
+    assert!(!report.is_valid());
+    let issues: Vec<(IssueKind, &str)> = report.issues.iter().map(|i| {
+        assert_eq!(Severity::Error, i.severity);
+        (i.kind.clone(), i.path.as_ref())
+    }).collect();
+    assert_eq!(vec!(
+        (IssueKind::EmptyRange, "parameters[0].range"),
+        (IssueKind::EmptyEquation, "variables[0].equation"),
+        (IssueKind::InvalidParameterIndex, "variables[1].equation[0].parameterIndices[0]"),
+        (IssueKind::InvalidVariableIndex, "variables[1].equation[1].evaluables[0]"),
+        (IssueKind::EmptyRampApproximation, "variables[1].equation[1].evaluables[1].approximation")
+    ), issues);
+    assert_eq!(Some("Empty range: 2 >= 1".to_string()), model.is_valid());
+}
+
+#[test]
+fn semantic_issues() {
+    let model = OdeModel { name: "Test".to_string(), parameters: vec!(),
+        variables: vec!(
+            Variable { name: "var1".to_string(), range: Range { min: 0.0, max: 10.0 },
+                thresholds: vec!(1.0, 5.0, 3.0, 3.0, 12.0), var_points: None,
+                equation: vec!(Summand { constant: ::std::f64::NAN, variable_indices: vec!(),
+                    parameter_indices: vec!(), evaluables: vec!(
+                        Evaluable::Hill { variable_index: 0, theta: 1.2, n: -1.0, a: 1.0, b: 2.0 },
+                        Evaluable::Ramp { variable_index: 0, low: 3.0, high: 3.0, a: 1.0, b: 2.0 },
+                        Evaluable::RampApproximation { variable_index: 0, approximation: vec!(
+                            Point { threshold: 2.0, value: 5.0 },
+                            Point { threshold: 2.0, value: 8.0 }
+                        )}
+                    )
+                })
+            }
+        )
+    };
+    let report = model.validate();
+    let errors: Vec<(IssueKind, &str)> = report.errors().iter().map(|i| {
+        (i.kind.clone(), i.path.as_ref())
+    }).collect();
+    assert_eq!(vec!(
+        (IssueKind::UnsortedThresholds, "variables[0].thresholds[2]"),
+        (IssueKind::DuplicateThreshold, "variables[0].thresholds[3]"),
+        (IssueKind::ThresholdOutOfRange, "variables[0].thresholds[4]"),
+        (IssueKind::NotFinite, "variables[0].equation[0].constant"),
+        (IssueKind::NegativeHillCoefficient, "variables[0].equation[0].evaluables[0].n"),
+        (IssueKind::EmptyRamp, "variables[0].equation[0].evaluables[1]"),
+        (IssueKind::UnsortedApproximation, "variables[0].equation[0].evaluables[2].approximation[1]")
+    ), errors);
+    let warnings: Vec<IssueKind> = report.warnings().iter().map(|i| i.kind.clone()).collect();
+    assert_eq!(vec!(IssueKind::MissingBoundaryThreshold, IssueKind::MissingBoundaryThreshold), warnings);
+}
+
+#[test]
+fn compile_reports_invalid_model() {
+    let model = OdeModel { name: "Test".to_string(), parameters: vec!(), variables: vec!() };
+    match model.compile() {
+        Err(CompileError::InvalidModel(report)) => assert_eq!(1, report.issues.len()),
+        _ => panic!["Invalid model compiled"]
+    }
+}
+
+#[test]
+fn capabilities_of_parametrised_model() {
+    let model = OdeModel { name: "Test".to_string(),
+        parameters: vec!(
+            Parameter { name: "p1".to_string(), range: Range { min: 0.0, max: 1.0 }},
+            Parameter { name: "p2".to_string(), range: Range { min: 0.0, max: 1.0 }}
+        ),
+        variables: vec!(
+            Variable { name: "var1".to_string(), range: Range { min: 0.0, max: 10.0 },
+                thresholds: vec!(0.0, 10.0), var_points: None,
+                equation: vec!(Summand { constant: 1.0, variable_indices: vec!(),
+                    parameter_indices: vec!(0), evaluables: vec!()
+                }, Summand { constant: -1.0, variable_indices: vec!(0),
+                    parameter_indices: vec!(1), evaluables: vec!()
+                })
+            }
+        )
+    }.compile().unwrap();
+    let capabilities = capabilities(&model);
+    assert_eq!(Err(CompileError::Parametrised { variable: 0 }), capabilities.order_0);
+    assert_eq!(Err(CompileError::MultipleParameters { variable: 0 }), capabilities.order_1);
+    assert_eq!(Ok(()), capabilities.order_n);
+    assert_eq!(Err(CompileError::MultipleParameters { variable: 0 }), Order1::divide(&model, &0, &vec![0]));
+    assert_eq!(CompileError::MultipleParameters { variable: 0 },
+        CheckerContext::<Order1>::new(model).err().unwrap());
+}
+
+#[test]
+fn single_threshold_is_a_warning() {
+    let model = OdeModel { name: "Test".to_string(), parameters: vec!(), variables: vec!(
+        Variable { name: "var1".to_string(), range: Range { min: 0.0, max: 10.0 },
+            thresholds: vec!(0.0), var_points: None,
+            equation: vec!(Summand { constant: 1.0, variable_indices: vec!(),
+                parameter_indices: vec!(), evaluables: vec!()
+            })
+        }
+    )};
+    let report = model.validate();
+    assert!(report.is_valid());
+    assert_eq!(vec!(IssueKind::SingleThreshold, IssueKind::MissingBoundaryThreshold),
+        report.warnings().iter().map(|i| i.kind.clone()).collect::<Vec<_>>());
+}